@@ -0,0 +1,75 @@
+// Benchmarks for the serialize/deserialize paths.
+// Run with `cargo bench`, optionally followed by a filter string, e.g. `cargo bench -- deserialize`.
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use serde_json_any_key::*;
+
+#[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Test {
+  pub a: i32,
+  pub b: i32
+}
+
+#[derive(Clone, Default, Deserialize, Serialize, PartialEq, Eq, Debug)]
+pub struct Nested {
+  #[serde(with = "any_key_map")]
+  map: HashMap<Test, Test>
+}
+
+// Deterministic data so that runs are comparable
+fn struct_map(len: usize) -> HashMap<Test, Test> {
+  (0..len as i32).map(|i| (Test {a: i, b: i.wrapping_mul(31)}, Test {a: i ^ 0x5555, b: -i})).collect()
+}
+
+fn string_map(len: usize) -> HashMap<String, i64> {
+  (0..len).map(|i| (format!("key-{:08x}", i.wrapping_mul(2654435761)), i as i64)).collect()
+}
+
+// Runs f until at least the target duration has elapsed and prints the mean time per iteration
+fn bench<T, F: FnMut() -> T>(filter: &Option<String>, name: &str, mut f: F) {
+  if let Some(filter) = filter {
+    if !name.contains(filter.as_str()) { return; }
+  }
+  let target = Duration::from_secs(2);
+  black_box(f()); // warm up
+  let start = Instant::now();
+  let mut iters = 0u32;
+  while start.elapsed() < target || iters < 3 {
+    black_box(f());
+    iters += 1;
+  }
+  println!("{:<40} {:>12.3?} / iter ({} iters)", name, start.elapsed() / iters, iters);
+}
+
+fn main() {
+  // `cargo bench` passes --bench to the harness; anything else is a filter
+  let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+
+  for len in [10_000, 1_000_000] {
+    let map = struct_map(len);
+    let ser = map.to_json_map().unwrap();
+    bench(&filter, &format!("serialize/struct/{}", len), || map.to_json_map().unwrap());
+    bench(&filter, &format!("serialize_cow/struct/{}", len), || map.to_json_map_cow().unwrap());
+    bench(&filter, &format!("deserialize/struct/{}", len), || json_to_map::<Test, Test>(&ser).unwrap());
+    bench(&filter, &format!("deserialize_iter/struct/{}", len), || {
+      json_to_iter::<Test, Test>(&ser).unwrap().map(|x| x.unwrap()).collect::<Vec<_>>()
+    });
+    bench(&filter, &format!("deserialize_btree/struct/{}", len), || json_to_btreemap::<Test, Test>(&ser).unwrap());
+    bench(&filter, &format!("consuming/struct/{}", len), || map.clone().into_json_map().unwrap());
+
+    let nested = Nested { map: map.clone() };
+    let nested_ser = serde_json::to_string(&nested).unwrap();
+    bench(&filter, &format!("serialize_with/struct/{}", len), || serde_json::to_string(&nested).unwrap());
+    bench(&filter, &format!("deserialize_with/struct/{}", len), || serde_json::from_str::<Nested>(&nested_ser).unwrap());
+
+    let strings = string_map(len);
+    let ser = strings.to_json_map().unwrap();
+    bench(&filter, &format!("serialize/string/{}", len), || strings.to_json_map().unwrap());
+    bench(&filter, &format!("serialize_cow/string/{}", len), || strings.to_json_map_cow().unwrap());
+    // plain serde_json, for comparison
+    bench(&filter, &format!("serialize_plain/string/{}", len), || serde_json::to_string(&strings).unwrap());
+    bench(&filter, &format!("deserialize/string/{}", len), || json_to_map::<String, i64>(&ser).unwrap());
+  }
+}
@@ -0,0 +1,26 @@
+// Run with `cargo +nightly fuzz run json_to_map` from the repository root.
+// The deserialization functions must return an error for any input, and never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_json_any_key::*;
+
+fuzz_target!(|data: &[u8]| {
+  let Ok(s) = std::str::from_utf8(data) else { return };
+
+  let _ = json_to_map::<(i32, i32), String>(s);
+  let _ = json_to_map::<i64, serde_json::Value>(s);
+  let _ = json_to_vec::<Vec<serde_json::Value>, serde_json::Value>(s);
+  let _ = json_to_vec_all::<f64, serde_json::Value>(s);
+  if let Ok(iter) = json_to_iter::<std::net::IpAddr, serde_json::Value>(s) {
+    let _ = iter.count();
+  }
+  let _ = JsonMapReader::<_, serde_json::Value, serde_json::Value>::new(data).count();
+
+  // anything which deserializes must serialize again, and be read back.
+  // The entries are not compared, as serde_json does not round-trip every float exactly without its float_roundtrip feature.
+  if let Ok(vec) = json_to_vec::<serde_json::Value, serde_json::Value>(s) {
+    let ser = vec.to_json_map().unwrap();
+    assert_eq!(json_to_vec_all::<serde_json::Value, serde_json::Value>(&ser).unwrap().len(), vec.len());
+  }
+});
@@ -1,39 +1,113 @@
-/// Apply the attribute `#[serde(with = "any_key_map")]` to de/serialize structs with nested maps that contain non-string keys.
-/// 
-/// This attribute supports any type that impls `IntoIterator<Item=(&K,&V)>` and `FromIterator<(K,V)>`.
-/// ```
-/// use std::collections::HashMap;
-/// use serde::{Serialize, Deserialize};
-/// use serde_json::Error;
-/// use serde_json_any_key::*;
-/// 
-/// #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
-/// pub struct Test {
-///   pub a: i32,
-///   pub b: i32
-/// }
-/// 
-/// #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
-/// pub struct WithNestedMap {
-///   #[serde(with = "any_key_map")]
-///   pub struct_map: HashMap<Test, Test>,
-///   #[serde(with = "any_key_map")]
-///   pub int_map: HashMap<i32, String>
-/// }
-/// 
-/// fn try_main() -> Result<(), Error> {
-/// let mut data: WithNestedMap = Default::default();
-/// data.struct_map.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
-/// data.int_map.insert(5, "foo".to_string());
-/// 
-/// // you can use the usual serde_json functions now
-/// let ser = serde_json::to_string(&data).unwrap();
-/// let deser: WithNestedMap = serde_json::from_str(&ser).unwrap();
-///
-/// assert_eq!(data, deser);
-/// Ok(()) }
-/// try_main().unwrap();
-/// ```
+//! Apply the attribute `#[serde(with = "any_key_map")]` to de/serialize structs with nested maps that contain non-string keys.
+//! 
+//! This attribute supports any type that impls `IntoIterator<Item=(&K,&V)>` and `FromIterator<(K,V)>`.
+//! Entries are written in the collection's iteration order, so a `BTreeMap` produces reproducible output sorted by the native `K` ordering.
+//! On deserialization, the collection's `FromIterator` impl determines the order, so a `BTreeMap` is sorted again regardless of the input order.
+//! ```
+//! use std::collections::HashMap;
+//! use serde::{Serialize, Deserialize};
+//! use serde_json::Error;
+//! use serde_json_any_key::*;
+//! 
+//! #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+//! pub struct Test {
+//!   pub a: i32,
+//!   pub b: i32
+//! }
+//! 
+//! #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+//! pub struct WithNestedMap {
+//!   #[serde(with = "any_key_map")]
+//!   pub struct_map: HashMap<Test, Test>,
+//!   #[serde(with = "any_key_map")]
+//!   pub int_map: HashMap<i32, String>
+//! }
+//! 
+//! fn try_main() -> Result<(), Error> {
+//! let mut data: WithNestedMap = Default::default();
+//! data.struct_map.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+//! data.int_map.insert(5, "foo".to_string());
+//! 
+//! // you can use the usual serde_json functions now
+//! let ser = serde_json::to_string(&data).unwrap();
+//! let deser: WithNestedMap = serde_json::from_str(&ser).unwrap();
+//!
+//! assert_eq!(data, deser);
+//! Ok(()) }
+//! try_main().unwrap();
+//! ```
+//!
+//! The attribute can be combined with `#[serde(flatten)]` to merge the map entries into the parent object.
+//! Note that every key of the parent object which does not belong to another field will then be parsed as a `K`,
+//! so unknown fields will cause a deserialization error.
+//! ```
+//! use std::collections::HashMap;
+//! use serde::{Serialize, Deserialize};
+//! use serde_json_any_key::*;
+//!
+//! #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+//! pub struct Config {
+//!   pub name: String,
+//!   #[serde(flatten, with = "any_key_map")]
+//!   pub ports: HashMap<u16, String>
+//! }
+//!
+//! let mut data = Config { name: "srv".to_string(), ..Default::default() };
+//! data.ports.insert(80, "http".to_string());
+//!
+//! let ser = serde_json::to_string(&data).unwrap();
+//! assert_eq!(ser, r#"{"name":"srv","80":"http"}"#);
+//! let deser: Config = serde_json::from_str(&ser).unwrap();
+//! assert_eq!(data, deser);
+//! ```
+//!
+//! When deserializing from a human-readable format such as JSON, an array of `[key, value]` pairs with native keys is also accepted,
+//! which eases migrating producers between the two forms. Serialization always writes a JSON map.
+//! ```
+//! use std::collections::HashMap;
+//! use serde::Deserialize;
+//! use serde_json_any_key::*;
+//!
+//! #[derive(Deserialize)]
+//! pub struct Routes {
+//!   #[serde(with = "any_key_map")]
+//!   pub map: HashMap<(i32, i32), String>
+//! }
+//!
+//! let from_map: Routes = serde_json::from_str(r#"{"map":{"[3,5]":"foo"}}"#).unwrap();
+//! let from_pairs: Routes = serde_json::from_str(r#"{"map":[[[3,5],"foo"]]}"#).unwrap();
+//! assert_eq!(from_map.map, from_pairs.map);
+//! ```
+//!
+//! As serde_json reports errors by position rather than by field, errors do not name the field, even with `#[serde(rename)]`.
+//! Instead, they state what was expected, and errors in an entry include its key and zero-based index, followed by the line and column
+//! in the document, such as `invalid key "five": expected ident at entry 0 at line 1 column 16`.
+//!
+//! A map wrapped in a newtype can be supported by applying the attribute to the newtype's own field.
+//! Newtype structs are transparent in JSON, so fields of the newtype need no attribute, and the output is the same as for the bare map.
+//! Alternatively, the attribute can be applied to the newtype field directly if `&Newtype` implements `IntoIterator<Item=(&K,&V)>`
+//! and `Newtype` implements `FromIterator<(K,V)>`, for example by forwarding to the inner map.
+//! ```
+//! use std::collections::HashMap;
+//! use serde::{Serialize, Deserialize};
+//! use serde_json_any_key::*;
+//!
+//! #[derive(Default, Serialize, Deserialize, PartialEq, Debug)]
+//! pub struct Registry(#[serde(with = "any_key_map")] HashMap<(i32, i32), String>);
+//!
+//! #[derive(Default, Serialize, Deserialize, PartialEq, Debug)]
+//! pub struct Config {
+//!   pub registry: Registry
+//! }
+//!
+//! let mut data = Config::default();
+//! data.registry.0.insert((3, 5), "foo".to_string());
+//!
+//! let ser = serde_json::to_string(&data).unwrap();
+//! assert_eq!(ser, r#"{"registry":{"[3,5]":"foo"}}"#);
+//! let deser: Config = serde_json::from_str(&ser).unwrap();
+//! assert_eq!(data, deser);
+//! ```
 
 use crate::serde_with_utils;
 use std::any::Any;
@@ -42,11 +116,31 @@ use serde::ser::{Serialize, Serializer};
 use serde::de::{Deserialize, Deserializer};
 
 /// See docs for [any_key_map](index.html).
+///
+/// This can also be called directly, to write a map with any `Serializer`, such as a `serde_json::Serializer`
+/// with a custom formatter or one which is part way through a larger document.
+/// It is equivalent to [serialize_map_into()](../fn.serialize_map_into.html), which also has variants for
+/// `Vec<(K,V)>` and consuming iterators.
+///
+/// # Examples
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_json_any_key::*;
+///
+/// let mut map = BTreeMap::<(i32, i32), i32>::new();
+/// map.insert((3, 5), 6);
+///
+/// let mut buf = Vec::new();
+/// let mut serializer = serde_json::Serializer::pretty(&mut buf);
+/// any_key_map::serialize(&map, &mut serializer).unwrap();
+///
+/// assert_eq!(String::from_utf8(buf).unwrap(), "{\n  \"[3,5]\": 6\n}");
+/// ```
 pub fn serialize<'s,S,C,K,V>(coll: C, serializer: S) -> Result<S::Ok,S::Error>
 where S: Serializer,
 C: IntoIterator<Item=(&'s K,&'s V)>,
 K: Serialize + Any + 's,
-V: Serialize + 's
+V: Serialize + ?Sized + 's
 {
   let mut iter = coll.into_iter();
   let wrap = crate::map_iter_to_json::SerializeMapIterWrapper {
@@ -62,8 +156,131 @@ pub fn deserialize<'d,D,C,K,V>(deserializer: D) -> Result<C, D::Error> where
   for<'de> K: Deserialize<'de> + Any + 'd,
   for<'de> V: Deserialize<'de> + 'd,
 {
-  // any_key_map and any_key_vec use the same deserialize function
-  serde_with_utils::deserialize::<'d,D,C,K,V>(deserializer)
+  serde_with_utils::deserialize_map_or_pairs::<'d,D,C,K,V>(deserializer)
+}
+
+/// Apply the attribute `#[serde(with = "any_key_map::nested")]` to de/serialize a map of maps, such as
+/// `HashMap<K, HashMap<K2,V>>`, where the keys of both levels are converted as by [any_key_map](../index.html).
+///
+/// Both the outer and the inner collections may be any type supported by `any_key_map`.
+/// ```
+/// use std::collections::{BTreeMap, HashMap};
+/// use serde::{Serialize, Deserialize};
+/// use serde_json_any_key::*;
+///
+/// #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+/// pub struct Index {
+///   #[serde(with = "any_key_map::nested")]
+///   pub by_cell: HashMap<(i32, i32), BTreeMap<(u8, u8), String>>
+/// }
+///
+/// let mut data: Index = Default::default();
+/// data.by_cell.entry((3, 5)).or_default().insert((1, 2), "foo".to_string());
+///
+/// let ser = serde_json::to_string(&data).unwrap();
+/// assert_eq!(ser, r#"{"by_cell":{"[3,5]":{"[1,2]":"foo"}}}"#);
+/// let deser: Index = serde_json::from_str(&ser).unwrap();
+/// assert_eq!(data, deser);
+/// ```
+///
+/// For deeper nesting, wrap the inner levels in a transparent newtype which applies the attribute itself:
+/// ```
+/// use std::collections::HashMap;
+/// use serde::{Serialize, Deserialize};
+/// use serde_json_any_key::*;
+///
+/// #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+/// #[serde(transparent)]
+/// pub struct Level3(#[serde(with = "any_key_map")] HashMap<(u8, u8), String>);
+///
+/// #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+/// pub struct Index {
+///   #[serde(with = "any_key_map::nested")]
+///   pub levels: HashMap<(i32, i32), HashMap<(i32, i32), Level3>>
+/// }
+///
+/// let mut data: Index = Default::default();
+/// data.levels.entry((1, 1)).or_default().entry((2, 2)).or_default().0.insert((3, 3), "foo".to_string());
+///
+/// let ser = serde_json::to_string(&data).unwrap();
+/// assert_eq!(ser, r#"{"levels":{"[1,1]":{"[2,2]":{"[3,3]":"foo"}}}}"#);
+/// let deser: Index = serde_json::from_str(&ser).unwrap();
+/// assert_eq!(data, deser);
+/// ```
+pub mod nested {
+  use crate::serde_with_utils;
+  use std::any::Any;
+  use std::cell::RefCell;
+  use std::marker::PhantomData;
+  use serde::ser::{Serialize, Serializer};
+  use serde::de::{Deserialize, Deserializer};
+
+  /// See docs for [nested](index.html).
+  pub fn serialize<'s,S,C,K,C2,K2,V>(coll: C, serializer: S) -> Result<S::Ok,S::Error>
+  where S: Serializer,
+  C: IntoIterator<Item=(&'s K,&'s C2)>,
+  &'s C2: IntoIterator<Item=(&'s K2,&'s V)>,
+  K: Serialize + Any + 's,
+  C2: 's,
+  K2: Serialize + Any + 's,
+  V: Serialize + 's
+  {
+    let wrap = crate::map_iter_to_json::SerializeMapIterWrapper {
+      iter: RefCell::new(coll.into_iter().map(|(k, inner)| (k, Inner { coll: inner, kv: PhantomData }))),
+    };
+    wrap.serialize(serializer)
+  }
+
+  /// See docs for [nested](index.html).
+  pub fn deserialize<'d,D,C,K,C2,K2,V>(deserializer: D) -> Result<C, D::Error> where
+    D: Deserializer<'d>,
+    C: FromIterator<(K,C2)>,
+    C2: FromIterator<(K2,V)>,
+    for<'de> K: Deserialize<'de> + Any + 'd,
+    for<'de> K2: Deserialize<'de> + Any,
+    for<'de> V: Deserialize<'de>,
+  {
+    let outer: Unwrap<C,C2> = serde_with_utils::deserialize::<'d,D,_,K,Inner<C2,K2,V>>(deserializer)?;
+    Ok(outer.0)
+  }
+
+  // An inner collection, which is de/serialized with any_key_map
+  struct Inner<C2,K2,V> {
+    coll: C2,
+    kv: PhantomData<fn() -> (K2,V)>
+  }
+
+  impl<'s,C2,K2,V> Serialize for Inner<&'s C2,K2,V> where
+    &'s C2: IntoIterator<Item=(&'s K2,&'s V)>,
+    K2: Serialize + Any + 's,
+    V: Serialize + 's
+  {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      super::serialize(self.coll, serializer)
+    }
+  }
+
+  impl<'de,C2,K2,V> Deserialize<'de> for Inner<C2,K2,V> where
+    C2: FromIterator<(K2,V)>,
+    for<'x> K2: Deserialize<'x> + Any,
+    for<'x> V: Deserialize<'x>
+  {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      let coll = serde_with_utils::deserialize(deserializer)?;
+      Ok(Inner { coll, kv: PhantomData })
+    }
+  }
+
+  // Collects the outer entries, unwrapping each inner collection without an intermediate buffer
+  struct Unwrap<C,C2>(C, PhantomData<C2>);
+
+  impl<C,K,C2,K2,V> FromIterator<(K,Inner<C2,K2,V>)> for Unwrap<C,C2> where
+    C: FromIterator<(K,C2)>
+  {
+    fn from_iter<I: IntoIterator<Item=(K,Inner<C2,K2,V>)>>(iter: I) -> Self {
+      Unwrap(iter.into_iter().map(|(k, inner)| (k, inner.coll)).collect(), PhantomData)
+    }
+  }
 }
 
 #[cfg(test)]
@@ -131,4 +348,220 @@ mod tests {
     let deser: SerdeWithMap = serde_json::from_str(&serialized).unwrap();
     assert_eq!(data, deser);
   }
+
+  #[test]
+  fn test_struct_serde_with_map_flatten() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithMap {
+      pub name: String,
+      #[serde(flatten, with = "any_key_map")]
+      pub inner: HashMap<Test,Test>
+    }
+    let mut data = SerdeWithMap {
+      name: "foo".to_string(),
+      inner: HashMap::new()
+    };
+    data.inner.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    let serialized = serde_json::to_string(&data).unwrap();
+    assert_eq!(serialized, "{\"name\":\"foo\",\"{\\\"a\\\":3,\\\"b\\\":5}\":{\"a\":7,\"b\":9}}");
+    let deser: SerdeWithMap = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+
+    // unknown fields of the parent are treated as map keys
+    let unknown = "{\"name\":\"foo\",\"bar\":{\"a\":7,\"b\":9}}";
+    assert!(serde_json::from_str::<SerdeWithMap>(unknown).is_err());
+  }
+
+  #[test]
+  fn test_path_serde_with_map() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithMap {
+      #[serde(with = "any_key_map")]
+      pub inner: HashMap<std::path::PathBuf, i32>
+    }
+    let mut data = SerdeWithMap {
+      inner: HashMap::new()
+    };
+    data.inner.insert(std::path::PathBuf::from("foo/bar"), 5);
+
+    let serialized = serde_json::to_string(&data).unwrap();
+    assert_eq!(serialized, "{\"inner\":{\"foo/bar\":5}}");
+    let deser: SerdeWithMap = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+  }
+
+  #[test]
+  fn test_int_serde_with_map_invalid_key() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithMap {
+      #[serde(with = "any_key_map")]
+      pub inner: HashMap<i32, Test>
+    }
+    let err = serde_json::from_str::<SerdeWithMap>("{\"inner\":{\"five\":{\"a\":6,\"b\":7}}}").unwrap_err();
+    assert_eq!(err.to_string(), "invalid key \"five\": expected ident at entry 0 at line 1 column 16");
+  }
+
+  #[test]
+  fn test_struct_serde_with_btreemap_order() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithMap {
+      #[serde(with = "any_key_map")]
+      pub inner: std::collections::BTreeMap<Test,Test>
+    }
+    let mut data = SerdeWithMap {
+      inner: std::collections::BTreeMap::new()
+    };
+    // native order differs from the order of the stringified keys
+    data.inner.insert(Test {a: 10, b: 0}, Test {a: 1, b: 1});
+    data.inner.insert(Test {a: 9, b: 0}, Test {a: 2, b: 2});
+    data.inner.insert(Test {a: -1, b: 0}, Test {a: 3, b: 3});
+    let serialized = serde_json::to_string(&data).unwrap();
+    // output follows the BTreeMap iteration order
+    assert_eq!(serialized, "{\"inner\":{\"{\\\"a\\\":-1,\\\"b\\\":0}\":{\"a\":3,\"b\":3},\"{\\\"a\\\":9,\\\"b\\\":0}\":{\"a\":2,\"b\":2},\"{\\\"a\\\":10,\\\"b\\\":0}\":{\"a\":1,\"b\":1}}}");
+    assert_eq!(serialized, serde_json::to_string(&data.clone()).unwrap());
+
+    let deser: SerdeWithMap = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+    let keys: Vec<i32> = deser.inner.keys().map(|k| k.a).collect();
+    assert_eq!(keys, vec![-1, 9, 10]);
+  }
+
+
+  #[test]
+  fn test_struct_serde_with_nested_map() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithNestedMap {
+      #[serde(with = "any_key_map::nested")]
+      pub inner: HashMap<Test, HashMap<Test, Test>>,
+      #[serde(with = "any_key_map::nested")]
+      pub pairs: std::collections::BTreeMap<i32, std::collections::BTreeMap<Test, String>>
+    }
+    let mut data = SerdeWithNestedMap {
+      inner: HashMap::new(),
+      pairs: Default::default()
+    };
+    data.inner.entry(Test {a: 1, b: 2}).or_default().insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.inner.entry(Test {a: 4, b: 4}).or_default();
+    let serialized = serde_json::to_string(&data).unwrap();
+    let deser: SerdeWithNestedMap = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+
+    let ser = "{\"inner\":{},\"pairs\":{\"5\":{\"{\\\"a\\\":1,\\\"b\\\":2}\":\"foo\"}}}";
+    let deser: SerdeWithNestedMap = serde_json::from_str(ser).unwrap();
+    assert_eq!(deser.pairs[&5][&Test {a: 1, b: 2}], "foo");
+
+    let err = serde_json::from_str::<SerdeWithNestedMap>("{\"inner\":{\"{\\\"a\\\":1,\\\"b\\\":2}\":{\"x\":{\"a\":1,\"b\":2}}},\"pairs\":{}}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key \"{\\\"a\\\":1,\\\"b\\\":2}\": invalid key \"x\""), "{}", err);
+  }
+
+
+  #[test]
+  fn test_struct_serde_with_nested_map_borrowed_values() {
+    use std::borrow::Cow;
+    // only the keys need to be 'static, so values may borrow
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct Borrowing<'a> {
+      #[serde(with = "any_key_map::nested")]
+      pub inner: HashMap<Test, HashMap<i32, Cow<'a, str>>>
+    }
+    let label = String::from("foo");
+    let mut data = Borrowing { inner: HashMap::new() };
+    data.inner.entry(Test {a: 1, b: 2}).or_default().insert(5, Cow::Borrowed(label.as_str()));
+    let serialized = serde_json::to_string(&data).unwrap();
+    assert_eq!(serialized, "{\"inner\":{\"{\\\"a\\\":1,\\\"b\\\":2}\":{\"5\":\"foo\"}}}");
+    let deser: Borrowing = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+  }
+
+  #[test]
+  fn test_struct_serde_with_map_from_pairs() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithMap {
+      #[serde(with = "any_key_map")]
+      pub inner: HashMap<Test, i32>
+    }
+    let mut data = SerdeWithMap {
+      inner: HashMap::new()
+    };
+    data.inner.insert(Test {a: 3, b: 5}, 6);
+    data.inner.insert(Test {a: 7, b: 9}, 10);
+
+    let from_pairs: SerdeWithMap = serde_json::from_str("{\"inner\":[[{\"a\":3,\"b\":5},6],[{\"a\":7,\"b\":9},10]]}").unwrap();
+    assert_eq!(data, from_pairs);
+    let from_empty: SerdeWithMap = serde_json::from_str("{\"inner\":[]}").unwrap();
+    assert!(from_empty.inner.is_empty());
+    // serialization still writes the map form
+    let serialized = serde_json::to_string(&from_pairs).unwrap();
+    assert!(serialized.starts_with("{\"inner\":{"), "{}", serialized);
+
+    let err = serde_json::from_str::<SerdeWithMap>("{\"inner\":[[{\"a\":3,\"b\":5},6],[{\"a\":7},10]]}").unwrap_err();
+    assert!(err.to_string().starts_with("missing field `b`") && err.to_string().contains(" at entry 1"), "{}", err);
+    let err = serde_json::from_str::<SerdeWithMap>("{\"inner\":5}").unwrap_err();
+    assert!(err.to_string().contains("expected a JSON map with stringified keys, or an array of [key, value] pairs"), "{}", err);
+  }
+
+
+  #[test]
+  fn test_struct_serde_with_newtype_map() {
+    #[derive(Clone, Default, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct Inner(#[serde(with = "any_key_map")] HashMap<Test, i32>);
+
+    #[derive(Clone, Default, PartialEq, Eq, Debug)]
+    struct Forwarding(HashMap<Test, i32>);
+
+    impl<'a> IntoIterator for &'a Forwarding {
+      type Item = (&'a Test, &'a i32);
+      type IntoIter = std::collections::hash_map::Iter<'a, Test, i32>;
+      fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+      }
+    }
+
+    impl FromIterator<(Test, i32)> for Forwarding {
+      fn from_iter<I: IntoIterator<Item=(Test, i32)>>(iter: I) -> Self {
+        Forwarding(iter.into_iter().collect())
+      }
+    }
+
+    #[derive(Clone, Default, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithNewtype {
+      pub inner: Inner,
+      #[serde(with = "any_key_map")]
+      pub forwarding: Forwarding
+    }
+    let mut data = SerdeWithNewtype::default();
+    data.inner.0.insert(Test {a: 3, b: 5}, 6);
+    data.forwarding.0.insert(Test {a: 3, b: 5}, 6);
+
+    let serialized = serde_json::to_string(&data).unwrap();
+    assert_eq!(serialized, "{\"inner\":{\"{\\\"a\\\":3,\\\"b\\\":5}\":6},\"forwarding\":{\"{\\\"a\\\":3,\\\"b\\\":5}\":6}}");
+    let deser: SerdeWithNewtype = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+  }
+
+
+  #[test]
+  fn test_renamed_serde_with_map_errors() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithRenamedMap {
+      pub name: String,
+      #[serde(rename = "routeTable", with = "any_key_map")]
+      pub routes: HashMap<Test, i32>
+    }
+    let mut data = SerdeWithRenamedMap { name: "srv".to_string(), routes: HashMap::new() };
+    data.routes.insert(Test {a: 3, b: 5}, 6);
+    let serialized = serde_json::to_string(&data).unwrap();
+    assert_eq!(serialized, "{\"name\":\"srv\",\"routeTable\":{\"{\\\"a\\\":3,\\\"b\\\":5}\":6}}");
+    assert_eq!(serde_json::from_str::<SerdeWithRenamedMap>(&serialized).unwrap(), data);
+
+    // the error names what was expected, and its position points into the renamed field
+    let err = serde_json::from_str::<SerdeWithRenamedMap>("{\"name\":\"srv\",\"routeTable\":5}").unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: integer `5`, expected a JSON map with stringified keys, or an array of [key, value] pairs at line 1 column 28");
+    let err = serde_json::from_str::<SerdeWithRenamedMap>("{\"name\":\"srv\",\"routeTable\":{\"{\\\"a\\\":3,\\\"b\\\":5}\":6,\"{\\\"a\\\":3}\":7}}").unwrap_err();
+    assert_eq!(err.to_string(), "invalid key \"{\\\"a\\\":3}\": missing field `b` at entry 1 at line 1 column 61");
+    let err = serde_json::from_str::<SerdeWithRenamedMap>("{\"name\":\"srv\",\"routeTable\":{\"{\\\"a\\\":3,\\\"b\\\":5}\":\"x\"}}").unwrap_err();
+    assert_eq!(err.to_string(), "invalid value for key \"{\\\"a\\\":3,\\\"b\\\":5}\": invalid type: string \"x\", expected i32 at entry 0 at line 1 column 52");
+    let err = serde_json::from_str::<SerdeWithRenamedMap>("{\"name\":\"srv\",\"routes\":{}}").unwrap_err();
+    assert!(err.to_string().starts_with("missing field `routeTable`"), "{}", err);
+  }
 }
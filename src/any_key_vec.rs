@@ -1,59 +1,59 @@
-/// Apply the attribute `#[serde(with = "any_key_vec")]` to de/serialize structs
-/// with nested `Vec<(K,V)>` that contain non-string keys.
-/// These Vecs will be serialized as JSON maps (as if they were a `HashMap<K,V>`).
-/// 
-/// This attribute supports any type that impls `IntoIterator<Item=&(K,V)>` and `FromIterator<(K,V)>`.
-/// ```
-/// use std::collections::HashMap;
-/// use serde::{Serialize, Deserialize};
-/// use serde_json::Error;
-/// use serde_json_any_key::*;
-/// 
-/// #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
-/// pub struct Test {
-///   pub a: i32,
-///   pub b: i32
-/// }
-/// 
-/// #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
-/// pub struct WithNestedVec {
-///   #[serde(with = "any_key_vec")]
-///   pub structs: Vec<(Test, Test)>,
-///   #[serde(with = "any_key_vec")]
-///   pub ints: Vec<(i32, String)>
-/// }
-/// 
-/// #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
-/// pub struct WithNestedMap {
-///   #[serde(with = "any_key_map")]
-///   pub structs: HashMap<Test, Test>,
-///   #[serde(with = "any_key_map")]
-///   pub ints: HashMap<i32, String>
-/// }
-/// 
-/// fn try_main() -> Result<(), Error> {
-/// let mut vec_data: WithNestedVec = Default::default();
-/// vec_data.structs.push((Test {a: 3, b: 5}, Test {a: 7, b: 9}));
-/// vec_data.ints.push((5, "foo".to_string()));
-/// 
-/// let mut map_data: WithNestedMap = Default::default();
-/// map_data.structs.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
-/// map_data.ints.insert(5, "foo".to_string());
-/// 
-/// // you can use the usual serde_json functions now
-/// // both structs produce the same JSON representation
-/// let ser_vec = serde_json::to_string(&vec_data).unwrap();
-/// let ser_map = serde_json::to_string(&map_data).unwrap();
-/// assert_eq!(ser_vec, ser_map);
-/// 
-/// // and can deserialize into each other
-/// let deser_vec: WithNestedVec = serde_json::from_str(&ser_map).unwrap();
-/// let deser_map: WithNestedMap = serde_json::from_str(&ser_vec).unwrap();
-/// assert_eq!(vec_data, deser_vec);
-/// assert_eq!(map_data, deser_map);
-/// Ok(()) }
-/// try_main().unwrap();
-/// ```
+//! Apply the attribute `#[serde(with = "any_key_vec")]` to de/serialize structs
+//! with nested `Vec<(K,V)>` that contain non-string keys.
+//! These Vecs will be serialized as JSON maps (as if they were a `HashMap<K,V>`).
+//! 
+//! This attribute supports any type that impls `IntoIterator<Item=&(K,V)>` and `FromIterator<(K,V)>`.
+//! ```
+//! use std::collections::HashMap;
+//! use serde::{Serialize, Deserialize};
+//! use serde_json::Error;
+//! use serde_json_any_key::*;
+//! 
+//! #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+//! pub struct Test {
+//!   pub a: i32,
+//!   pub b: i32
+//! }
+//! 
+//! #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+//! pub struct WithNestedVec {
+//!   #[serde(with = "any_key_vec")]
+//!   pub structs: Vec<(Test, Test)>,
+//!   #[serde(with = "any_key_vec")]
+//!   pub ints: Vec<(i32, String)>
+//! }
+//! 
+//! #[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+//! pub struct WithNestedMap {
+//!   #[serde(with = "any_key_map")]
+//!   pub structs: HashMap<Test, Test>,
+//!   #[serde(with = "any_key_map")]
+//!   pub ints: HashMap<i32, String>
+//! }
+//! 
+//! fn try_main() -> Result<(), Error> {
+//! let mut vec_data: WithNestedVec = Default::default();
+//! vec_data.structs.push((Test {a: 3, b: 5}, Test {a: 7, b: 9}));
+//! vec_data.ints.push((5, "foo".to_string()));
+//! 
+//! let mut map_data: WithNestedMap = Default::default();
+//! map_data.structs.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+//! map_data.ints.insert(5, "foo".to_string());
+//! 
+//! // you can use the usual serde_json functions now
+//! // both structs produce the same JSON representation
+//! let ser_vec = serde_json::to_string(&vec_data).unwrap();
+//! let ser_map = serde_json::to_string(&map_data).unwrap();
+//! assert_eq!(ser_vec, ser_map);
+//! 
+//! // and can deserialize into each other
+//! let deser_vec: WithNestedVec = serde_json::from_str(&ser_map).unwrap();
+//! let deser_map: WithNestedMap = serde_json::from_str(&ser_vec).unwrap();
+//! assert_eq!(vec_data, deser_vec);
+//! assert_eq!(map_data, deser_map);
+//! Ok(()) }
+//! try_main().unwrap();
+//! ```
 
 use crate::serde_with_utils;
 use std::any::Any;
@@ -62,6 +62,8 @@ use serde::ser::{Serialize, Serializer};
 use serde::de::{Deserialize, Deserializer};
 
 /// See docs for [any_key_vec](index.html).
+///
+/// Like [any_key_map::serialize()](../any_key_map/fn.serialize.html), this can also be called directly with any `Serializer`.
 pub fn serialize<'s,S,C,K,V>(coll: C, serializer: S) -> Result<S::Ok, S::Error>
 where S: Serializer,
 C: IntoIterator<Item=&'s (K,V)>,
@@ -82,7 +84,7 @@ pub fn deserialize<'d,D,C,K,V>(deserializer: D) -> Result<C, D::Error> where
   for<'de> K: Deserialize<'de> + Any + 'd,
   for<'de> V: Deserialize<'de> + 'd,
 {
-  // any_key_map and any_key_vec use the same deserialize function
+  // only a map is accepted here, while any_key_map also accepts an array of pairs
   serde_with_utils::deserialize::<'d,D,C,K,V>(deserializer)
 }
 
@@ -149,4 +151,36 @@ mod tests {
     let deser: SerdeWithVec = serde_json::from_str(&serialized).unwrap();
     assert_eq!(data, deser);
   }
+
+  #[test]
+  fn test_int_serde_with_vec_flatten() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithVec {
+      pub name: String,
+      #[serde(flatten, with = "any_key_vec")]
+      pub inner: Vec<(i32, Test)>
+    }
+    let mut data = SerdeWithVec {
+      name: "foo".to_string(),
+      inner: vec![]
+    };
+    data.inner.push((5, Test {a: 6, b: 7}));
+
+    let serialized = serde_json::to_string(&data).unwrap();
+    assert_eq!(serialized, "{\"name\":\"foo\",\"5\":{\"a\":6,\"b\":7}}");
+    let deser: SerdeWithVec = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+  }
+
+
+  #[test]
+  fn test_serde_with_vec_rejects_pairs() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithVec {
+      #[serde(with = "any_key_vec")]
+      pub inner: Vec<(i32, i32)>
+    }
+    let err = serde_json::from_str::<SerdeWithVec>("{\"inner\":[[5,6]]}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid type: sequence, expected a JSON map with stringified keys"), "{}", err);
+  }
 }
@@ -1,5 +1,5 @@
 
-use std::any::{Any, TypeId};
+use std::any::Any;
 use std::cell::RefCell;
 use serde::ser::{Serialize, Serializer, SerializeMap, Error};
 
@@ -59,11 +59,67 @@ V: Serialize,
   /// Ok(()) }
   /// try_main().unwrap();
   /// ```
+  ///
+  /// For `Copy` data, a by-value iterator such as `iter().copied()` can be used.
+  /// Only the iterator is consumed, so the underlying collection may be serialized again.
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let arr = [(1, 2.5), (3, 4.5)];
+  /// let ser1 = arr.iter().copied().into_json_map().unwrap();
+  /// let ser2 = arr.iter().copied().into_json_map().unwrap();
+  ///
+  /// assert_eq!(ser1, "{\"1\":2.5,\"3\":4.5}");
+  /// assert_eq!(ser1, ser2);
+  /// ```
+  ///
+  /// Draining iterators work the same way. The collection is emptied but keeps its allocation for reuse.
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut pending = HashMap::<(i32, i32), String>::with_capacity(64);
+  /// pending.insert((3, 5), "foo".to_string());
+  ///
+  /// let ser = pending.drain().into_json_map().unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"[3,5]":"foo"}"#);
+  /// assert!(pending.is_empty());
+  /// assert!(pending.capacity() >= 64);
+  /// ```
   fn into_json_map(self) -> Result<String, serde_json::Error> {
     serde_json::to_string(&SerializeConsumingIterWrapper {
       iter: RefCell::new(self.into_iter())
     })
   }
+
+  /// Same as [into_json_map()](#method.into_json_map), but the entries are written sorted by the native `K: Ord` ordering.
+  ///
+  /// The entries are buffered and sorted before serialization, so numeric keys are written as
+  /// `"1","2","10"` rather than in the lexical order of their strings. Entries with equal keys keep their relative order.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<i32, &str>::new();
+  /// map.insert(10, "c");
+  /// map.insert(1, "a");
+  /// map.insert(2, "b");
+  /// let ser = map.into_json_map_sorted_native().unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"1":"a","2":"b","10":"c"}"#);
+  /// ```
+  fn into_json_map_sorted_native(self) -> Result<String, serde_json::Error> where
+  K: Ord
+  {
+    let mut entries: Vec<(K,V)> = self.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    serde_json::to_string(&SerializeConsumingIterWrapper {
+      iter: RefCell::new(entries.into_iter())
+    })
+  }
 }
 
 impl<'a,K,V,T: IntoIterator<Item=(K,V)>> ConsumingIterToJson<'a,K,V> for T where
@@ -73,10 +129,111 @@ V: Serialize,
 <Self as IntoIterator>::IntoIter: 'a
 { }
 
-struct SerializeConsumingIterWrapper<K,V,I> where
+/// Same as [into_json_map()](trait.ConsumingIterToJson.html#method.into_json_map), but the collection is returned along with the error
+/// if serialization fails, so that it is not lost.
+///
+/// The entries are serialized by reference, so the collection is only dropped once serialization has succeeded.
+/// This is implemented for `HashMap<K,V>`, `BTreeMap<K,V>`, and `Vec<(K,V)>`.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json_any_key::*;
+///
+/// let mut map = HashMap::<(i32, i32), f64>::new();
+/// map.insert((3, 5), 1.5);
+/// assert_eq!(map.try_into_json_map().unwrap(), r#"{"[3,5]":1.5}"#);
+///
+/// let vec: Vec<(f64, i32)> = vec![(f64::NAN, 1)];
+/// let (vec, err) = vec.try_into_json_map().unwrap_err();
+/// assert_eq!(err.to_string(), "float key must be finite");
+/// assert_eq!(vec.len(), 1);
+/// ```
+pub trait TryIntoJsonMap: Sized {
+  /// Serializes the collection to a JSON map, or returns it unchanged together with the error.
+  fn try_into_json_map(self) -> Result<String, (Self, serde_json::Error)>;
+}
+
+impl<K,V,S> TryIntoJsonMap for std::collections::HashMap<K,V,S> where
+K: Serialize + Any,
+V: Serialize
+{
+  fn try_into_json_map(self) -> Result<String, (Self, serde_json::Error)> {
+    match crate::MapIterToJson::to_json_map(self.iter()) {
+      Ok(ser) => Ok(ser),
+      Err(e) => Err((self, e))
+    }
+  }
+}
+
+impl<K,V> TryIntoJsonMap for std::collections::BTreeMap<K,V> where
+K: Serialize + Any,
+V: Serialize
+{
+  fn try_into_json_map(self) -> Result<String, (Self, serde_json::Error)> {
+    match crate::MapIterToJson::to_json_map(self.iter()) {
+      Ok(ser) => Ok(ser),
+      Err(e) => Err((self, e))
+    }
+  }
+}
+
+impl<K,V> TryIntoJsonMap for Vec<(K,V)> where
+K: Serialize + Any,
+V: Serialize
+{
+  fn try_into_json_map(self) -> Result<String, (Self, serde_json::Error)> {
+    match crate::VecIterToJson::to_json_map(self.iter()) {
+      Ok(ser) => Ok(ser),
+      Err(e) => Err((self, e))
+    }
+  }
+}
+
+/// Implements `Serialize` for an owned `Iterator<Item=(K,V)>`, writing it as a map with the same keys as [into_json_map()](trait.ConsumingIterToJson.html#method.into_json_map).
+///
+/// This allows the entries of a moved iterator to be embedded inside another `Serialize` impl, or passed to any serde serializer.
+/// The iterator is consumed by the first call to `serialize()`; any later call will produce an empty map.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde::Serialize;
+/// use serde_json_any_key::*;
+///
+/// let mut map = HashMap::<(i32, i32), String>::new();
+/// map.insert((3, 5), "foo".to_string());
+///
+/// #[derive(Serialize)]
+/// #[serde(bound = "")]
+/// struct Export<I: Iterator<Item=((i32, i32), String)>> {
+///   version: u32,
+///   entries: SerializeConsumingIterWrapper<(i32, i32), String, I>
+/// }
+///
+/// let export = Export {
+///   version: 1,
+///   entries: SerializeConsumingIterWrapper::new(map.into_iter())
+/// };
+/// let ser = serde_json::to_string(&export).unwrap();
+///
+/// assert_eq!(ser, r#"{"version":1,"entries":{"[3,5]":"foo"}}"#);
+/// ```
+pub struct SerializeConsumingIterWrapper<K,V,I> where
 I: Iterator<Item=(K,V)>,
 {
-  pub iter: RefCell<I>
+  pub(crate) iter: RefCell<I>
+}
+
+impl<K,V,I> SerializeConsumingIterWrapper<K,V,I> where
+I: Iterator<Item=(K,V)>,
+{
+  /// Wraps any `IntoIterator<Item=(K,V)>`, taking ownership of it.
+  pub fn new<T: IntoIterator<IntoIter=I>>(iter: T) -> Self {
+    SerializeConsumingIterWrapper {
+      iter: RefCell::new(iter.into_iter())
+    }
+  }
 }
 
 impl<K,V,I> Serialize for SerializeConsumingIterWrapper<K,V,I> where
@@ -87,23 +244,92 @@ impl<K,V,I> Serialize for SerializeConsumingIterWrapper<K,V,I> where
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
     S: Serializer
   {
-    let mut ser_map = serializer.serialize_map(None)?;
-    let mut iter = self.iter.borrow_mut();
-    // handle strings specially so they don't get escaped and wrapped inside another string
-    // compiler seems to be able to optimize this branch away statically
-    if TypeId::of::<K>() == TypeId::of::<String>() {
-      while let Some((k, v)) = iter.next() {
-        let s = (&k as &dyn Any).downcast_ref::<String>().ok_or(S::Error::custom("Failed to serialize String as string"))?;
-        ser_map.serialize_entry(s, &v)?;
-      }
-    } else {
-      while let Some((k, v)) = iter.next() {
-        ser_map.serialize_entry(match &serde_json::to_string(&k)
-        {
-          Ok(key_string) => key_string,
-          Err(e) => { return Err(e).map_err(S::Error::custom); }
-        }, &v)?;
-      }
+    // a Serialize impl which re-enters this wrapper would otherwise panic on the second borrow
+    let mut iter = self.iter.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    let mut ser_map = serializer.serialize_map(crate::serde_with_utils::len_hint(&*iter))?;
+    for (k, v) in iter.by_ref() {
+      crate::serde_with_utils::serialize_map_entries(&mut ser_map, std::iter::once((&k, v)))?;
+    }
+    ser_map.end()
+  }
+}
+
+/// Serialize an `IntoIterator<Item=(K,V)>` as a JSON map into the given `Serializer`, consuming the iterator and converting the keys as
+/// [into_json_map()](trait.ConsumingIterToJson.html#method.into_json_map) does.
+///
+/// See [serialize_map_into()](fn.serialize_map_into.html) for details.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json_any_key::*;
+///
+/// let mut map = HashMap::<(i32, i32), String>::new();
+/// map.insert((3, 5), "foo".to_string());
+///
+/// let mut buf = Vec::new();
+/// serialize_consuming_into(map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+///
+/// assert_eq!(String::from_utf8(buf).unwrap(), r#"{"[3,5]":"foo"}"#);
+/// ```
+pub fn serialize_consuming_into<S,I,K,V>(iter: I, serializer: S) -> Result<S::Ok, S::Error> where
+  S: Serializer,
+  I: IntoIterator<Item=(K,V)>,
+  K: Serialize + Any,
+  V: Serialize
+{
+  SerializeConsumingIterWrapper::new(iter).serialize(serializer)
+}
+
+/// Serialize an `IntoIterator<Item=Result<(K,V), E>>` to a JSON map, stopping at the first `Err`.
+///
+/// This allows fallible producers to be serialized directly, without first collecting into a `Result<Vec<(K,V)>, E>`.
+/// The first error is converted into the returned `serde_json::Error`, keeping its message.
+///
+/// # Examples
+/// ```
+/// use serde_json_any_key::*;
+///
+/// let lines = ["3 foo", "7 bar"];
+/// let ser = try_iter_to_json_map(lines.iter().map(|line| {
+///   let (k, v) = line.split_once(' ').ok_or("missing separator")?;
+///   Ok::<_, Box<dyn std::error::Error>>((k.parse::<i32>()?, v))
+/// })).unwrap();
+/// assert_eq!(ser, r#"{"3":"foo","7":"bar"}"#);
+///
+/// let err = try_iter_to_json_map(vec![Ok((1, 2)), Err("producer failed")]).unwrap_err();
+/// assert_eq!(err.to_string(), "producer failed");
+/// ```
+pub fn try_iter_to_json_map<I,K,V,E>(iter: I) -> Result<String, serde_json::Error> where
+I: IntoIterator<Item=Result<(K,V), E>>,
+K: Serialize + Any,
+V: Serialize,
+E: Into<Box<dyn std::error::Error>>
+{
+  serde_json::to_string(&SerializeTryIterWrapper {
+    iter: RefCell::new(iter.into_iter())
+  })
+}
+
+struct SerializeTryIterWrapper<I> {
+  iter: RefCell<I>
+}
+
+impl<K,V,E,I> Serialize for SerializeTryIterWrapper<I> where
+  I: Iterator<Item=Result<(K,V), E>>,
+  K: Serialize + Any,
+  V: Serialize,
+  E: Into<Box<dyn std::error::Error>>
+{
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+    S: Serializer
+  {
+    let mut iter = self.iter.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    let mut ser_map = serializer.serialize_map(crate::serde_with_utils::len_hint(&*iter))?;
+    for entry in iter.by_ref() {
+      let (k, v) = entry.map_err(|e| S::Error::custom(e.into()))?;
+      let key_string = crate::serde_with_utils::key_to_string(&k).map_err(S::Error::custom)?;
+      ser_map.serialize_entry(&key_string, &v)?;
     }
     ser_map.end()
   }
@@ -0,0 +1,113 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use serde::de::Deserialize;
+
+/// Construct a map from a JSON map string, as the counterpart of [to_json_map()](trait.MapIterToJson.html#method.to_json_map).
+///
+/// `HashMap::<K,V>::from_json_map(&s)` is equivalent to [json_to_map()](fn.json_to_map.html).
+/// The same conversion is available as a method on strings through [FromJsonMapStr](trait.FromJsonMapStr.html).
+///
+/// # Examples
+/// ```
+/// use std::collections::{HashMap, BTreeMap};
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[3,5]":"foo","[7,9]":"bar"}"#;
+///
+/// let hash = HashMap::<(i32, i32), String>::from_json_map(ser)?;
+/// let btree = BTreeMap::<(i32, i32), String>::from_json_map(ser)?;
+///
+/// assert_eq!(hash[&(3, 5)], "foo");
+/// assert_eq!(btree.into_iter().collect::<HashMap<_, _>>(), hash);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub trait FromJsonMap: Sized {
+  /// Deserializes a JSON map string into `Self`. See [json_to_map()](fn.json_to_map.html) for the error format.
+  fn from_json_map(str: &str) -> Result<Self, serde_json::Error>;
+}
+
+impl<K,V> FromJsonMap for HashMap<K,V> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  fn from_json_map(str: &str) -> Result<Self, serde_json::Error> {
+    crate::json_to_map(str)
+  }
+}
+
+impl<K,V> FromJsonMap for BTreeMap<K,V> where
+for<'de> K: Deserialize<'de> + Ord + Any,
+for<'de> V: Deserialize<'de>
+{
+  fn from_json_map(str: &str) -> Result<Self, serde_json::Error> {
+    crate::json_to_iter(str)?.collect()
+  }
+}
+
+/// Blanket impl [try_from_json_map()](trait.TryFromJsonMap.html#method.try_from_json_map) for all `FromIterator<(K,V)>` types.
+pub trait TryFromJsonMap<K,V>: FromIterator<(K,V)> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  /// Deserializes a JSON map string into any collection of `(K,V)` entries, equivalent to [json_to_collection()](fn.json_to_collection.html).
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::BTreeMap;
+  /// use serde_json::Error;
+  /// use serde_json_any_key::*;
+  ///
+  /// fn try_main() -> Result<(), Error> {
+  /// let ser = r#"{"[7,9]":"bar","[3,5]":"foo"}"#;
+  ///
+  /// let btree = BTreeMap::<(i32, i32), String>::try_from_json_map(ser)?;
+  /// let vec = Vec::<((i32, i32), String)>::try_from_json_map(ser)?;
+  ///
+  /// assert_eq!(btree.into_iter().collect::<Vec<_>>(), vec);
+  /// Ok(()) }
+  /// try_main().unwrap();
+  /// ```
+  fn try_from_json_map(str: &str) -> Result<Self, serde_json::Error> {
+    crate::json_to_collection(str)
+  }
+}
+
+impl<C,K,V> TryFromJsonMap<K,V> for C where
+C: FromIterator<(K,V)>,
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{ }
+
+/// Blanket impl [from_json_map()](trait.FromJsonMapStr.html#method.from_json_map) for all string types.
+pub trait FromJsonMapStr: AsRef<str> {
+  /// Deserializes this string into any [FromJsonMap](trait.FromJsonMap.html) type, which is usually inferred.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json::Error;
+  /// use serde_json_any_key::*;
+  ///
+  /// fn try_main() -> Result<(), Error> {
+  /// let mut map = HashMap::<(i32, i32), String>::new();
+  /// map.insert((3, 5), "foo".to_string());
+  ///
+  /// let ser = map.to_json_map()?;
+  /// let deser: HashMap<(i32, i32), String> = ser.from_json_map()?;
+  ///
+  /// assert_eq!(map, deser);
+  /// Ok(()) }
+  /// try_main().unwrap();
+  /// ```
+  // named to mirror to_json_map(), even though it borrows self
+  #[allow(clippy::wrong_self_convention)]
+  fn from_json_map<T: FromJsonMap>(&self) -> Result<T, serde_json::Error> {
+    T::from_json_map(self.as_ref())
+  }
+}
+
+impl<T: AsRef<str> + ?Sized> FromJsonMapStr for T { }
@@ -0,0 +1,108 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use serde::ser::{Serialize, Serializer, SerializeMap, Error};
+
+/// Serialize an iterator of entry guards to a JSON map, such as those yielded by concurrent maps or lock-protected entries.
+///
+/// Iterators over concurrent maps like `dashmap::DashMap` yield guard objects rather than `(&K,&V)`, so
+/// [to_json_map()](trait.MapIterToJson.html#method.to_json_map) does not apply to them.
+/// Here, `entry` borrows the key and value out of each guard, and each guard is held only while its entry is being serialized.
+///
+/// The keys are converted exactly as [to_json_map()](trait.MapIterToJson.html#method.to_json_map) would.
+///
+/// # Consistency
+/// The output is **not** an atomic snapshot of the whole map. Entries are written one at a time, each while its own guard is held,
+/// so every entry is internally consistent, but the map may change while it is being serialized:
+/// - an entry modified before the iterator reaches it is written with its new value, and one modified afterwards keeps its old value;
+/// - entries inserted or removed during serialization may or may not appear, depending on where the iterator is;
+/// - with `dashmap::DashMap`, the iterator holds a read lock on the shard it is in, so writers to that shard wait until it moves on.
+///   Modifying the same map from the current thread while it is being serialized, such as from a `Serialize` impl, can therefore deadlock.
+///
+/// If a consistent snapshot is needed, hold a lock over the whole map for the duration instead,
+/// for example by calling [to_json_map()](trait.MapIterToJson.html#method.to_json_map) on the read guard of an `RwLock<HashMap<K,V>>`,
+/// or clone the concurrent map first.
+///
+/// # Examples
+/// ```
+/// use std::sync::Mutex;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let entries = vec![Mutex::new(((3, 5), "foo".to_string())), Mutex::new(((7, 9), "bar".to_string()))];
+///
+/// let ser = guards_to_json_map(entries.iter().map(|m| m.lock().unwrap()), |g| (&g.0, &g.1))?;
+///
+/// assert_eq!(ser, r#"{"[3,5]":"foo","[7,9]":"bar"}"#);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+///
+/// Each entry is read when the iterator reaches it, so a write to a later entry during serialization is included.
+/// Guarding the whole map with one lock gives a snapshot instead:
+/// ```
+/// use std::collections::BTreeMap;
+/// use std::sync::RwLock;
+/// use serde_json_any_key::*;
+///
+/// let entries = vec![RwLock::new(((3, 5), 1)), RwLock::new(((7, 9), 1))];
+/// let ser = guards_to_json_map(entries.iter().enumerate().map(|(i, lock)| {
+///   if i == 0 {
+///     // another writer updates the second entry while the first is being written
+///     entries[1].write().unwrap().1 = 2;
+///   }
+///   lock.read().unwrap()
+/// }), |g| (&g.0, &g.1)).unwrap();
+/// assert_eq!(ser, r#"{"[3,5]":1,"[7,9]":2}"#);
+///
+/// let map = RwLock::new(BTreeMap::from([((3, 5), 1), ((7, 9), 1)]));
+/// let ser = map.read().unwrap().to_json_map().unwrap();
+/// assert_eq!(ser, r#"{"[3,5]":1,"[7,9]":1}"#);
+/// ```
+///
+/// With `dashmap`, the entry accessor is simply `pair()`, and the consistency rules above apply per shard:
+/// ```ignore
+/// let ser = guards_to_json_map(dash.iter(), |r| r.pair())?;
+/// ```
+pub fn guards_to_json_map<I,G,K,V,F>(iter: I, entry: F) -> Result<String, serde_json::Error> where
+I: IntoIterator<Item=G>,
+K: Serialize + Any,
+V: Serialize + ?Sized,
+F: for<'g> FnMut(&'g G) -> (&'g K, &'g V)
+{
+  serde_json::to_string(&SerializeGuardIterWrapper {
+    inner: RefCell::new((iter.into_iter(), entry)),
+    kv: PhantomData
+  })
+}
+
+struct SerializeGuardIterWrapper<I,F,K,V> where
+K: ?Sized,
+V: ?Sized
+{
+  inner: RefCell<(I,F)>,
+  kv: PhantomData<(Box<K>,Box<V>)>
+}
+
+impl<I,G,K,V,F> Serialize for SerializeGuardIterWrapper<I,F,K,V> where
+  I: Iterator<Item=G>,
+  K: Serialize + Any,
+  V: Serialize + ?Sized,
+  F: for<'g> FnMut(&'g G) -> (&'g K, &'g V)
+{
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+    S: Serializer
+  {
+    // a Serialize impl which re-enters this wrapper would otherwise panic on the second borrow
+    let mut inner = self.inner.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    let (iter, entry) = &mut *inner;
+    let mut ser_map = serializer.serialize_map(crate::serde_with_utils::len_hint(iter))?;
+    for guard in iter.by_ref() {
+      let (k, v) = entry(&guard);
+      let key_string = crate::serde_with_utils::key_to_string(k).map_err(S::Error::custom)?;
+      ser_map.serialize_entry(&key_string, v)?;
+    }
+    ser_map.end()
+  }
+}
@@ -0,0 +1,40 @@
+use std::any::Any;
+use std::hash::Hash;
+use serde::de::Deserialize;
+
+/// Reads a JSON Lines document where each line is a JSON map, such as those written by
+/// [to_json_map_line()](trait.MapIterToJson.html#method.to_json_map_line).
+///
+/// Returns an iterator which lazily parses one line at a time with [json_to_map()](fn.json_to_map.html).
+/// Blank lines are skipped, and both `\n` and `\r\n` line endings are accepted.
+/// A line which fails to parse yields an error, and iteration can continue with the following lines.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let mut log = String::new();
+/// for i in 0..3 {
+///   let mut map = HashMap::<(i32, i32), i32>::new();
+///   map.insert((i, i), i);
+///   log.push_str(&map.to_json_map_line()?);
+/// }
+///
+/// let maps = json_map_lines::<(i32, i32), i32>(&log).collect::<Result<Vec<_>, _>>()?;
+///
+/// assert_eq!(maps.len(), 3);
+/// assert_eq!(maps[2][&(2, 2)], 2);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_map_lines<'a,K,V>(str: &'a str) -> impl Iterator<Item=Result<std::collections::HashMap<K,V>, serde_json::Error>> + 'a where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de> + 'a
+{
+  str.lines()
+    .filter(|line| !line.trim().is_empty())
+    .map(crate::json_to_map)
+}
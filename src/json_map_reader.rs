@@ -0,0 +1,223 @@
+use std::any::Any;
+use std::io::BufRead;
+use std::marker::PhantomData;
+use serde::de::{Deserialize, Error};
+
+/// Incrementally reads a JSON map from any `std::io::BufRead`, one entry at a time. It implements `Iterator<Item = Result<(K,V), serde_json::Error>>`.
+///
+/// Unlike [json_to_iter()](fn.json_to_iter.html), the document is never loaded into a `serde_json::Value`.
+/// Only the bytes of the current entry are buffered, so arbitrarily large maps can be processed with constant memory.
+/// The keys are parsed exactly as [json_to_iter()](fn.json_to_iter.html) would.
+///
+/// Entries are yielded in document order, and duplicate keys are preserved.
+/// After the first error, the iterator is exhausted.
+///
+/// Readers without their own buffer, such as a `File`, can be wrapped in a `std::io::BufReader`.
+///
+/// # Examples
+/// ```
+/// use std::io::BufReader;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let file = BufReader::new(r#"{"[3,5]":"foo", "[7,9]":"bar"}"#.as_bytes());
+///
+/// let mut total = 0;
+/// for entry in JsonMapReader::<_, (i32, i32), String>::new(file) {
+///   let ((a, b), _value) = entry?;
+///   total += a + b;
+/// }
+///
+/// assert_eq!(total, 24);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub struct JsonMapReader<R: BufRead,K,V> {
+  bytes: std::io::Bytes<R>,
+  peeked: Option<u8>,
+  state: ReaderState,
+  key_buf: Vec<u8>,
+  val_buf: Vec<u8>,
+  kv: PhantomData<(K,V)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReaderState {
+  Start,
+  First,
+  Rest,
+  Done
+}
+
+impl<R: BufRead,K,V> JsonMapReader<R,K,V> {
+  /// Creates an iterator over the entries of the JSON map which will be read from `reader`.
+  pub fn new(reader: R) -> Self {
+    JsonMapReader {
+      bytes: reader.bytes(),
+      peeked: None,
+      state: ReaderState::Start,
+      key_buf: vec![],
+      val_buf: vec![],
+      kv: PhantomData
+    }
+  }
+
+  fn next_byte_opt(&mut self) -> Result<Option<u8>, serde_json::Error> {
+    match self.peeked.take() {
+      Some(b) => Ok(Some(b)),
+      None => self.bytes.next().transpose().map_err(serde_json::Error::io)
+    }
+  }
+
+  fn next_byte(&mut self) -> Result<u8, serde_json::Error> {
+    self.next_byte_opt()?.ok_or_else(|| serde_json::Error::custom("EOF while parsing a JSON map"))
+  }
+
+  fn next_non_whitespace(&mut self) -> Result<u8, serde_json::Error> {
+    loop {
+      let b = self.next_byte()?;
+      if !b.is_ascii_whitespace() { return Ok(b); }
+    }
+  }
+
+  // Reads the remainder of a string whose opening quote has already been pushed onto buf
+  fn read_string(&mut self, buf: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    loop {
+      let b = self.next_byte()?;
+      buf.push(b);
+      match b {
+        b'\\' => { buf.push(self.next_byte()?); },
+        b'"' => { return Ok(()); },
+        _ => {}
+      }
+    }
+  }
+
+  // Reads the bytes of a single value, starting with `first`, without interpreting them.
+  // The value is validated later by serde_json.
+  fn read_value(&mut self, first: u8, buf: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    buf.push(first);
+    match first {
+      b'"' => self.read_string(buf),
+      b'{' | b'[' => {
+        let mut depth = 1;
+        while depth > 0 {
+          let b = self.next_byte()?;
+          buf.push(b);
+          match b {
+            b'"' => { self.read_string(buf)?; },
+            b'{' | b'[' => { depth += 1; },
+            b'}' | b']' => { depth -= 1; },
+            _ => {}
+          }
+        }
+        Ok(())
+      },
+      _ => {
+        // numbers and literals end at the next delimiter, which is pushed back
+        while let Some(b) = self.next_byte_opt()? {
+          if b == b',' || b == b'}' || b == b']' || b.is_ascii_whitespace() {
+            self.peeked = Some(b);
+            break;
+          }
+          buf.push(b);
+        }
+        Ok(())
+      }
+    }
+  }
+
+  fn read_entry(&mut self) -> Result<Option<(K,V)>, serde_json::Error> where
+  for<'de> K: Deserialize<'de> + Any,
+  for<'de> V: Deserialize<'de>
+  {
+    if self.state == ReaderState::Start {
+      if self.next_non_whitespace()? != b'{' {
+        return Err(serde_json::Error::custom("Value is not a JSON map"));
+      }
+      self.state = ReaderState::First;
+    }
+    let mut b = self.next_non_whitespace()?;
+    if b == b'}' {
+      self.state = ReaderState::Done;
+      return Ok(None);
+    }
+    if self.state == ReaderState::Rest {
+      if b != b',' {
+        return Err(serde_json::Error::custom("expected `,` or `}` after map entry"));
+      }
+      b = self.next_non_whitespace()?;
+    }
+    if b != b'"' {
+      return Err(serde_json::Error::custom("key must be a string"));
+    }
+
+    let mut key_buf = std::mem::take(&mut self.key_buf);
+    let mut val_buf = std::mem::take(&mut self.val_buf);
+    key_buf.clear();
+    val_buf.clear();
+    key_buf.push(b);
+    self.read_string(&mut key_buf)?;
+    if self.next_non_whitespace()? != b':' {
+      return Err(serde_json::Error::custom("expected `:` after map key"));
+    }
+    let first = self.next_non_whitespace()?;
+    self.read_value(first, &mut val_buf)?;
+
+    let key: String = serde_json::from_slice(&key_buf)?;
+    let (key_obj, val_obj) = crate::serde_with_utils::entry_from_parts(key, serde_json::from_slice(&val_buf))?;
+    self.key_buf = key_buf;
+    self.val_buf = val_buf;
+    self.state = ReaderState::Rest;
+    Ok(Some((key_obj, val_obj)))
+  }
+}
+
+impl<R: BufRead,K,V> Iterator for JsonMapReader<R,K,V> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  type Item = Result<(K,V), serde_json::Error>;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.state == ReaderState::Done {
+      return None;
+    }
+    match self.read_entry() {
+      Ok(entry) => entry.map(Ok),
+      Err(e) => {
+        self.state = ReaderState::Done;
+        Some(Err(e))
+      }
+    }
+  }
+}
+
+/// Reverses to_json_map(), lazily reading the entries from any `std::io::Read` as the returned iterator advances.
+///
+/// This is a shorthand for a [JsonMapReader](struct.JsonMapReader.html) over a `std::io::BufReader`, so only the current entry
+/// is held in memory, and maps which do not fit in memory can be processed. Reading stops at the first error.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let file = r#"{"[3,5]":"foo","[7,9]":"bar"}"#.as_bytes();
+///
+/// let mut iter = json_to_iter_streaming::<_, (i32, i32), String>(file);
+/// assert_eq!(iter.next().unwrap()?, ((3, 5), "foo".to_string()));
+/// let rest: HashMap<(i32, i32), String> = iter.collect::<Result<_, _>>()?;
+/// assert_eq!(rest[&(7, 9)], "bar");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_iter_streaming<R,K,V>(reader: R) -> JsonMapReader<std::io::BufReader<R>,K,V> where
+R: std::io::Read,
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  JsonMapReader::new(std::io::BufReader::new(reader))
+}
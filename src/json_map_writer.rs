@@ -0,0 +1,71 @@
+use std::any::Any;
+use std::io::Write;
+use serde::ser::Serialize;
+
+/// Incrementally writes a JSON map to any `std::io::Write`, one entry at a time.
+///
+/// The keys are converted to strings exactly as [to_json_map()](trait.MapIterToJson.html#method.to_json_map) would,
+/// so the output can be read back with any of the deserialization functions.
+/// This allows exporting entries as they are produced, without the whole collection ever existing in memory.
+///
+/// Call [finish()](#method.finish) once all entries have been written to emit the closing brace.
+///
+/// # Examples
+/// ```
+/// use std::sync::mpsc;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let (tx, rx) = mpsc::channel::<((i32, i32), String)>();
+/// std::thread::spawn(move || {
+///   tx.send(((3, 5), "foo".to_string())).unwrap();
+///   tx.send(((7, 9), "bar".to_string())).unwrap();
+/// });
+///
+/// let mut writer = JsonMapWriter::new(Vec::new());
+/// for (k, v) in rx {
+///   writer.write_entry(&k, &v)?;
+/// }
+/// let ser = String::from_utf8(writer.finish()?).unwrap();
+///
+/// assert_eq!(ser, r#"{"[3,5]":"foo","[7,9]":"bar"}"#);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub struct JsonMapWriter<W: Write> {
+  writer: W,
+  empty: bool
+}
+
+impl<W: Write> JsonMapWriter<W> {
+  /// Creates a writer which will write a JSON map to `writer`. Nothing is written until the first entry.
+  pub fn new(writer: W) -> Self {
+    JsonMapWriter {
+      writer,
+      empty: true
+    }
+  }
+
+  /// Serializes a single entry and writes it to the underlying writer.
+  pub fn write_entry<K,V>(&mut self, key: &K, value: &V) -> Result<(), serde_json::Error> where
+  K: Serialize + Any,
+  V: Serialize + ?Sized
+  {
+    let key_string = crate::serde_with_utils::key_to_string(key)?;
+    let separator: &[u8] = if self.empty { b"{" } else { b"," };
+    self.writer.write_all(separator).map_err(serde_json::Error::io)?;
+    self.empty = false;
+    serde_json::to_writer(&mut self.writer, &*key_string)?;
+    self.writer.write_all(b":").map_err(serde_json::Error::io)?;
+    serde_json::to_writer(&mut self.writer, value)
+  }
+
+  /// Writes the closing brace, flushes, and returns the underlying writer.
+  pub fn finish(mut self) -> Result<W, serde_json::Error> {
+    let close: &[u8] = if self.empty { b"{}" } else { b"}" };
+    self.writer.write_all(close).map_err(serde_json::Error::io)?;
+    self.writer.flush().map_err(serde_json::Error::io)?;
+    Ok(self.writer)
+  }
+}
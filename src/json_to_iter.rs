@@ -1,5 +1,5 @@
 
-use std::any::{Any, TypeId};
+use std::any::Any;
 use serde::ser::Error;
 use serde::de::Deserialize;
 
@@ -41,18 +41,231 @@ pub fn json_to_iter<K,V>(str: &str) -> Result<impl Iterator<Item = Result<(K,V),
 for<'de> K: Deserialize<'de> + Any,
 for<'de> V: Deserialize<'de>
 {
-  let json_value = serde_json::from_str(&str)?;
-  let json_map = match json_value {
-    serde_json::Value::Object(map) => map,
-          _ => { return Err(serde_json::Error::custom("Value is not a JSON map")); },
-  };
-  Ok(JsonToTupleIter {
-    iter: json_map.into_iter(),
+  let json_map = crate::serde_with_utils::parse_json_map(str)?;
+  Ok(map_to_iter(json_map))
+}
+
+/// Same as [json_to_iter()](fn.json_to_iter.html), but reads from an already parsed `serde_json::Map`
+/// instead of a string, which avoids a serialize/parse round-trip.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let doc: serde_json::Value = serde_json::from_str(r#"{"inner":{"[3,5]":"foo"}}"#)?;
+/// let inner = match doc {
+///   serde_json::Value::Object(mut o) => o.remove("inner"),
+///   _ => None
+/// };
+/// let inner = match inner {
+///   Some(serde_json::Value::Object(map)) => map,
+///   _ => panic!("not a map")
+/// };
+///
+/// let deser: HashMap<(i32, i32), String> = map_to_iter(inner).collect::<Result<_, _>>()?;
+/// assert_eq!(deser[&(3, 5)], "foo");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn map_to_iter<K,V>(map: serde_json::Map<String, serde_json::Value>) -> impl Iterator<Item = Result<(K,V), serde_json::Error>> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  JsonToTupleIter {
+    iter: map.into_iter(),
     kv: std::marker::PhantomData
-  })
+  }
+}
+
+/// Same as [json_to_iter()](fn.json_to_iter.html), but entries which fail to deserialize are skipped instead of returned as errors.
+///
+/// An error is still returned if the document is not a JSON map. To find out which entries were skipped,
+/// use [json_to_vec_lossy()](fn.json_to_vec_lossy.html) instead.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[3,5]":"foo","[7,9":"corrupt","[1,2]":5}"#;
+/// let deser: HashMap<(i32, i32), String> = json_to_iter_lossy(ser)?.collect();
+///
+/// assert_eq!(deser.len(), 1);
+/// assert_eq!(deser[&(3, 5)], "foo");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_iter_lossy<K,V>(str: &str) -> Result<impl Iterator<Item = (K,V)>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  Ok(json_to_iter(str)?.filter_map(Result::ok))
+}
+
+/// Same as [json_to_iter()](fn.json_to_iter.html), but applies `f` to each entry as it is deserialized.
+///
+/// `f` is only called for entries which deserialized successfully, and errors are passed through unchanged.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"Foo":[3,5],"BAR":[7,9]}"#;
+/// let deser: HashMap<String, i32> = json_to_iter_map(ser, |(k, v): (String, (i32, i32))| (k.to_lowercase(), v.0 + v.1))?
+///   .collect::<Result<_, _>>()?;
+///
+/// assert_eq!(deser["foo"], 8);
+/// assert_eq!(deser["bar"], 16);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_iter_map<K,V,K2,V2,F>(str: &str, mut f: F) -> Result<impl Iterator<Item = Result<(K2,V2), serde_json::Error>>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>,
+F: FnMut((K,V)) -> (K2,V2)
+{
+  Ok(json_to_iter(str)?.map(move |entry| entry.map(&mut f)))
+}
+
+/// Same as [json_to_iter_lossy()](fn.json_to_iter_lossy.html), but also returns the raw entries which failed to deserialize.
+///
+/// # Examples
+/// ```
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[3,5]":"foo","[7,9":"corrupt","[1,2]":5}"#;
+/// let (entries, skipped) = json_to_vec_lossy::<(i32, i32), String>(ser)?;
+///
+/// assert_eq!(entries, vec![((3, 5), "foo".to_string())]);
+/// assert_eq!(skipped.len(), 2);
+/// assert_eq!(skipped["[7,9"], "corrupt");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn json_to_vec_lossy<K,V>(str: &str) -> Result<(Vec<(K,V)>, serde_json::Map<String, serde_json::Value>), serde_json::Error> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  let json_map = crate::serde_with_utils::parse_json_map(str)?;
+  let mut entries = vec![];
+  let mut skipped = serde_json::Map::new();
+  for (key, val) in json_map {
+    let key_obj: Result<K, _> = crate::serde_with_utils::string_to_key(key.as_str());
+    match (key_obj, V::deserialize(&val)) {
+      (Ok(k), Ok(v)) => entries.push((k, v)),
+      _ => { skipped.insert(key, val); }
+    }
+  }
+  Ok((entries, skipped))
+}
+
+/// Reverses to_json_map(), collecting the entries into any `FromIterator<(K,V)>` type.
+///
+/// This includes, but is not limited to, `HashMap`, `BTreeMap`, `Vec<(K,V)>`, and stack-allocated types such as
+/// `smallvec::SmallVec<[(K,V); N]>`. Collection stops at the first error.
+///
+/// Fixed-capacity types such as `arrayvec::ArrayVec` may panic when collecting too many items;
+/// use [json_to_collection_bounded()](fn.json_to_collection_bounded.html) for those.
+///
+/// # Examples
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[3,5]":"foo","[7,9]":"bar"}"#;
+///
+/// let deser: BTreeMap<(i32, i32), String> = json_to_collection(ser)?;
+/// assert_eq!(deser[&(7, 9)], "bar");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_collection<C,K,V>(str: &str) -> Result<C, serde_json::Error> where
+C: FromIterator<(K,V)>,
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  json_to_iter(str)?.collect()
+}
+
+/// Same as [json_to_collection()](fn.json_to_collection.html), but returns an error instead of collecting
+/// if the JSON map has more than `capacity` entries.
+///
+/// The entry count is checked before any entry is deserialized, so fixed-capacity targets such as
+/// `arrayvec::ArrayVec<(K,V), N>` are never overfilled.
+///
+/// # Examples
+/// ```
+/// use serde_json_any_key::*;
+///
+/// let ser = r#"{"1":"a","2":"b","3":"c"}"#;
+///
+/// let ok = json_to_collection_bounded::<Vec<(i32, String)>, _, _>(ser, 4);
+/// assert_eq!(ok.unwrap().len(), 3);
+///
+/// let err = json_to_collection_bounded::<Vec<(i32, String)>, _, _>(ser, 2);
+/// assert_eq!(err.unwrap_err().to_string(), "JSON map has 3 entries, which exceeds the capacity of 2");
+/// ```
+pub fn json_to_collection_bounded<C,K,V>(str: &str, capacity: usize) -> Result<C, serde_json::Error> where
+C: FromIterator<(K,V)>,
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  let json_map = crate::serde_with_utils::parse_json_map(str)?;
+  if json_map.len() > capacity {
+    return Err(serde_json::Error::custom(format_args!("JSON map has {} entries, which exceeds the capacity of {}", json_map.len(), capacity)));
+  }
+  map_to_iter(json_map).collect()
+}
+
+/// Reverses to_json_map(), passing each `(K,V)` entry to the provided callback as it is deserialized.
+///
+/// No collection is built, which is useful when the entries are only forwarded elsewhere.
+/// Iteration stops at the first error, whether it is returned by deserialization or by the callback.
+///
+/// # Examples
+/// ```
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"5":"foo","6":"bar"}"#;
+///
+/// let mut total = 0;
+/// json_for_each(ser, |(k, v): (i32, String)| {
+///   total += k;
+///   assert!(v == "foo" || v == "bar");
+///   Ok(())
+/// })?;
+///
+/// assert_eq!(total, 11);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_for_each<K,V,F>(str: &str, mut f: F) -> Result<(), serde_json::Error> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>,
+F: FnMut((K,V)) -> Result<(), serde_json::Error>
+{
+  for entry in json_to_iter::<K,V>(str)? {
+    f(entry?)?;
+  }
+  Ok(())
 }
 
-/// Return type of [json_to_iter()](fn.json_to_iter.html). It implements `Iterator<Item = Result<(K,V), serde_json::Error>>`. 
+/// Return type of [json_to_iter()](fn.json_to_iter.html) and [map_to_iter()](fn.map_to_iter.html). It implements `Iterator<Item = Result<(K,V), serde_json::Error>>`. 
 struct JsonToTupleIter<K,V> {
   iter: serde_json::map::IntoIter,
   kv: std::marker::PhantomData<(K,V)>,
@@ -66,25 +279,7 @@ for<'de> V: Deserialize<'de>
   type Item = Result<(K,V), serde_json::Error>;
   fn next(&mut self) -> Option<Self::Item> {
     match self.iter.next() {
-      Some(a) => {
-        // handle strings specially as they are not objects
-        // compiler seems to be able to optimize this branch away statically
-        let key_obj: K = match TypeId::of::<K>() == TypeId::of::<String>() {
-          true => match <K as Deserialize>::deserialize(serde_json::Value::from(a.0)) {
-            Ok(k) => k,
-            Err(e) => { return Some(Err(e)); }
-          },
-          false => match serde_json::from_str(&a.0) {
-            Ok(k) => k,
-            Err(e) => { return Some(Err(e)); }
-          }
-        };
-        let val_obj: V = match <V as Deserialize>::deserialize(a.1) {
-          Ok(v) => v,
-          Err(e) => { return Some(Err(e)); }
-        };
-        Some(Ok((key_obj, val_obj)))
-      },
+      Some(a) => Some(crate::serde_with_utils::entry_from_parts(a.0, <V as Deserialize>::deserialize(a.1))),
       None => None
     }
   }
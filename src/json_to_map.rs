@@ -1,11 +1,18 @@
 
-use std::any::{Any, TypeId};
+use std::any::Any;
 use std::hash::Hash;
+use std::io::Read;
 use serde::ser::Error;
 use serde::de::Deserialize;
 
 /// Reverses to_json_map(), returning a `HashMap<K,V>`.
 ///
+/// The input may be any string type, such as `&str`, `String`, or `Cow<str>`.
+///
+/// # Errors
+/// If an entry cannot be deserialized, the error message begins with either `invalid key "<key>": `
+/// or `invalid value for key "<key>": `, so that the failing side of the entry can be identified.
+///
 /// # Examples
 /// ```
 /// use std::collections::HashMap;
@@ -30,27 +37,832 @@ use serde::de::Deserialize;
 /// Ok(()) }
 /// try_main().unwrap();
 /// ```
-pub fn json_to_map<'a,K,V>(str: &'a str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+pub fn json_to_map<K,V>(str: impl AsRef<str>) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str.as_ref())?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v| { map.insert(k, v); })?;
+  Ok(map)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but replaces the contents of an existing `target` map instead of returning a new one.
+///
+/// The map is cleared and refilled in place, keeping its allocation, which suits reloading a map held behind a lock.
+///
+/// # Errors
+/// On error, `target` is left empty, and the error is in the same format as [json_to_map()](fn.json_to_map.html).
+/// To keep the old contents on failure, deserialize with [json_to_map()](fn.json_to_map.html) and swap the result in instead.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let mut map = HashMap::<(i32, i32), String>::new();
+/// map.insert((1, 1), "old".to_string());
+///
+/// reload_map_from_json(&mut map, r#"{"[3,5]":"foo"}"#)?;
+/// assert_eq!(map.len(), 1);
+/// assert_eq!(map[&(3, 5)], "foo");
+///
+/// assert!(reload_map_from_json(&mut map, r#"{"[3,5]":5}"#).is_err());
+/// assert!(map.is_empty());
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn reload_map_from_json<K,V,S>(target: &mut std::collections::HashMap<K,V,S>, str: &str) -> Result<(), serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>,
+S: std::hash::BuildHasher
+{
+  target.clear();
+  let res = (|| {
+    let o = crate::serde_with_utils::parse_json_map(str)?;
+    target.reserve(o.len());
+    crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v| { target.insert(k, v); })
+  })();
+  if res.is_err() {
+    target.clear();
+  }
+  res
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but errors also name the type that the key or value was being deserialized into,
+/// as given by `std::any::type_name()`.
+///
+/// This helps to diagnose data which was written with a different key or value type than the one being requested.
+/// The type name is only meant for diagnostics, and its exact format is not guaranteed.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json_any_key::*;
+///
+/// let err = json_to_map_with_type_names::<i32, String>(r#"{"[3,5]":"foo"}"#).unwrap_err();
+/// assert!(err.to_string().starts_with(r#"invalid key "[3,5]": "#));
+/// assert!(err.to_string().ends_with("(key type is `i32`)"));
+///
+/// let err = json_to_map_with_type_names::<(i32, i32), u8>(r#"{"[3,5]":"foo"}"#).unwrap_err();
+/// assert!(err.to_string().ends_with("(value type is `u8`)"));
+/// ```
+pub fn json_to_map_with_type_names<K,V>(str: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
 for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
 for<'de> V: Deserialize<'de>
 {
   let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
-  let v: serde_json::Value = serde_json::from_str(&str)?;
-  let o = v.as_object().ok_or(serde_json::Error::custom("Value is not a JSON map"))?;
-  // handle strings specially as they are not objects
-  // compiler seems to be able to optimize this branch away statically
-  if TypeId::of::<K>() == TypeId::of::<String>() {
-    for (key, val) in o.iter() {
-      let key_obj: K = <K as Deserialize>::deserialize(serde_json::Value::from(key.as_str()))?;
-      let val_obj: V = <V as Deserialize>::deserialize(val)?;
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  let key_fn = |key: &str| crate::serde_with_utils::string_to_key(key)
+    .map_err(|e| serde_json::Error::custom(format_args!("{} (key type is `{}`)", e, std::any::type_name::<K>())));
+  crate::serde_with_utils::for_each_entry(&o, key_fn, |k, v: WithTypeName<V>| { map.insert(k, v.0); })?;
+  Ok(map)
+}
+
+/// Reverses to_json_map() for several documents, merging all of their entries into one `HashMap<K,V>`.
+///
+/// When a key is already present, `on_conflict` is called with the key, the existing value, and the new value,
+/// and the value it returns is kept. The inputs are processed in order, and entries within each input in key order.
+///
+/// # Errors
+/// Errors are in the same format as [json_to_map()](fn.json_to_map.html), followed by ` in input <index>`,
+/// where the index into `inputs` is zero-based.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let shard1 = r#"{"[3,5]":1,"[7,9]":2}"#;
+/// let shard2 = r#"{"[3,5]":10}"#;
+///
+/// let merged: HashMap<(i32, i32), i32> = merge_json_maps(&[shard1, shard2], |_k, old, new| old + new)?;
+/// assert_eq!(merged[&(3, 5)], 11);
+/// assert_eq!(merged[&(7, 9)], 2);
+///
+/// let err = merge_json_maps::<(i32, i32), i32, _>(&[shard1, "{"], |_k, _old, new| new).unwrap_err();
+/// assert!(err.to_string().ends_with(" in input 1"));
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn merge_json_maps<K,V,F>(inputs: &[&str], mut on_conflict: F) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>,
+F: FnMut(&K, V, V) -> V
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  for (index, input) in inputs.iter().enumerate() {
+    let in_input = |e: serde_json::Error| serde_json::Error::custom(format_args!("{} in input {}", e, index));
+    for entry in crate::json_to_iter::<K,V>(input).map_err(in_input)? {
+      let (key_obj, val_obj) = entry.map_err(in_input)?;
+      let val_obj = match map.remove(&key_obj) {
+        Some(existing) => on_conflict(&key_obj, existing, val_obj),
+        None => val_obj
+      };
       map.insert(key_obj, val_obj);
     }
-  } else {
-    for (key, val) in o.iter() {
-      let key_obj: K = serde_json::from_str(key)?;
-      let val_obj: V = <V as Deserialize>::deserialize(val)?;
-      map.insert(key_obj, val_obj);
+  }
+  Ok(map)
+}
+
+/// Reverses [to_json_map_with_meta()](trait.MapIterToJson.html#method.to_json_map_with_meta), returning the metadata and a `HashMap<K,V>`.
+///
+/// The reserved `"__meta"` entry is deserialized into `M`, and is not included in the map.
+///
+/// # Errors
+/// Returns an error if the `"__meta"` entry is missing, in addition to the errors of [json_to_map()](fn.json_to_map.html).
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"__meta":{"version":2},"[3,5]":"foo"}"#;
+/// let (meta, map): (serde_json::Value, HashMap<(i32, i32), String>) = json_to_map_with_meta(ser)?;
+///
+/// assert_eq!(meta["version"], 2);
+/// assert_eq!(map.len(), 1);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_with_meta<M,K,V>(str: &str) -> Result<(M, std::collections::HashMap<K,V>), serde_json::Error> where
+for<'de> M: Deserialize<'de>,
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut o = crate::serde_with_utils::parse_json_map(str)?;
+  let meta = o.remove(crate::serde_with_utils::META_KEY).ok_or(serde_json::Error::custom("missing \"__meta\" key"))?;
+  let meta_obj: M = <M as Deserialize>::deserialize(meta).map_err(|e| crate::serde_with_utils::invalid_value(crate::serde_with_utils::META_KEY, e))?;
+  let map = crate::map_to_iter(o).collect::<Result<_, _>>()?;
+  Ok((meta_obj, map))
+}
+
+/// Reverses to_json_map(), returning a `BTreeMap<K,V>`.
+///
+/// Unlike collecting [json_to_iter()](fn.json_to_iter.html) into a `BTreeMap`, the document is streamed without an intermediate `serde_json::Value`,
+/// and keys without escape sequences are parsed directly from the input, so no `String` is allocated for keys such as integers or structs.
+/// If a key appears more than once, the last value is kept. Errors are followed by ` at entry <index>`, as for [json_to_vec_all()](fn.json_to_vec_all.html).
+///
+/// # Examples
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"10":"c","9":"b","1":"a"}"#;
+/// let deser: BTreeMap<u64, String> = json_to_btreemap(ser)?;
+///
+/// assert_eq!(deser.into_iter().collect::<Vec<_>>(), vec![(1, "a".to_string()), (9, "b".to_string()), (10, "c".to_string())]);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_btreemap<K,V>(str: &str) -> Result<std::collections::BTreeMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + Ord + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut deserializer = serde_json::Deserializer::from_str(str);
+  let map = crate::serde_with_utils::deserialize_btreemap(&mut deserializer)?;
+  deserializer.end()?;
+  Ok(map)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but for integer keys, which must be written in canonical form.
+///
+/// serde_json already rejects leading zeros and plus signs such as `"007"` and `"+7"`, but accepts surrounding whitespace and `"-0"`,
+/// so `"7"` and `" 7"` would be read as the same key. Here, each key must be `0`, or an optional `-` followed by digits without a leading zero,
+/// which is checked before the key is parsed.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json_any_key::*;
+///
+/// let deser: HashMap<i32, String> = json_to_map_strict_numeric(r#"{"7":"foo","-10":"bar"}"#).unwrap();
+/// assert_eq!(deser[&-10], "bar");
+///
+/// assert!(json_to_map::<i32, String>(r#"{" 7":"foo"}"#).is_ok());
+/// let err = json_to_map_strict_numeric::<i32, String>(r#"{" 7":"foo"}"#).unwrap_err();
+/// assert_eq!(err.to_string(), r#"invalid key " 7": key is not a canonical integer"#);
+/// ```
+pub fn json_to_map_strict_numeric<K,V>(str: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  let key_fn = |key: &str| {
+    let digits = key.strip_prefix('-').unwrap_or(key);
+    let canonical = key == "0" || (!digits.is_empty() && !digits.starts_with('0') && digits.bytes().all(|b| b.is_ascii_digit()));
+    if !canonical {
+      return Err(crate::serde_with_utils::invalid_key(key, "key is not a canonical integer"));
+    }
+    crate::serde_with_utils::string_to_key(key)
+  };
+  crate::serde_with_utils::for_each_entry(&o, key_fn, |k, v| { map.insert(k, v); })?;
+  Ok(map)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but returns the map wrapped in an `Arc` so that it can be shared immediately.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let shared: Arc<HashMap<i32, String>> = json_to_arc_map(r#"{"5":"foo"}"#)?;
+/// let clone = Arc::clone(&shared);
+/// std::thread::spawn(move || assert_eq!(clone[&5], "foo")).join().unwrap();
+///
+/// assert_eq!(shared[&5], "foo");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_arc_map<K,V>(str: &str) -> Result<std::sync::Arc<std::collections::HashMap<K,V>>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  json_to_map(str).map(std::sync::Arc::new)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but values which fail to deserialize are replaced by `V::default()`.
+///
+/// Returns the map along with the keys whose values fell back to the default.
+/// Keys which fail to deserialize are still reported as an error.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// // written by a newer version which changed the value of key 7 to a string
+/// let ser = r#"{"5":10,"7":"ten"}"#;
+/// let (deser, fallbacks): (HashMap<i32, i32>, Vec<i32>) = json_to_map_with_default(ser)?;
+///
+/// assert_eq!(deser[&5], 10);
+/// assert_eq!(deser[&7], 0);
+/// assert_eq!(fallbacks, vec![7]);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_with_default<K,V>(str: &str) -> Result<(std::collections::HashMap<K,V>, Vec<K>), serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Clone + Any,
+for<'de> V: Deserialize<'de> + Default
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let mut fallbacks: Vec<K> = vec![];
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k: K, v: OrDefault<V>| {
+    let val_obj = match v.0 {
+      Some(v) => v,
+      None => {
+        fallbacks.push(k.clone());
+        V::default()
+      }
+    };
+    map.insert(k, val_obj);
+  })?;
+  Ok((map, fallbacks))
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but each key is passed through `f` after it is deserialized,
+/// and the result is used as the key of the returned map.
+///
+/// This allows converting keys to a new type (for example during a schema migration) without a second pass over the map.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// // old keys were (major, minor) tuples, new keys are a single version number
+/// let ser = r#"{"[1,2]":"foo","[2,0]":"bar"}"#;
+/// let deser: HashMap<u32, String> = json_to_map_with(ser, |(major, minor): (u32, u32)| major * 100 + minor)?;
+///
+/// assert_eq!(deser[&102], "foo");
+/// assert_eq!(deser[&200], "bar");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_with<K,V,K2,F>(str: &str, mut f: F) -> Result<std::collections::HashMap<K2,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>,
+K2: std::cmp::Eq + Hash,
+F: FnMut(K) -> K2
+{
+  let mut map: std::collections::HashMap<K2,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v| { map.insert(f(k), v); })?;
+  Ok(map)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but values which are JSON strings are parsed again as JSON before being deserialized into `V`.
+///
+/// This handles documents where the values were double-encoded, i.e. each value is a string containing serialized JSON.
+/// Values which are not strings are deserialized normally. Note that this means a plain string value is always parsed as JSON,
+/// so a `V` of `String` would need its values to be quoted twice.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde::Deserialize;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// #[derive(Deserialize, PartialEq, Eq, Debug)]
+/// pub struct Test {
+///   pub a: i32,
+///   pub b: i32
+/// }
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"5":"{\"a\":7,\"b\":9}","6":{"a":1,"b":2}}"#;
+/// let deser: HashMap<i32, Test> = json_to_map_nested_value(ser)?;
+///
+/// assert_eq!(deser[&5], Test {a: 7, b: 9});
+/// assert_eq!(deser[&6], Test {a: 1, b: 2});
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_nested_value<K,V>(str: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v: NestedJson<V>| { map.insert(k, v.0); })?;
+  Ok(map)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but the map is located within the document by the JSON Pointer `pointer` (RFC 6901).
+///
+/// This reads maps which are wrapped in an envelope, such as `{"data": {...}}`, without extracting and re-serializing them.
+/// An empty pointer refers to the whole document.
+///
+/// # Errors
+/// Returns an error if there is no value at `pointer`, or if the value there is not a JSON map.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"status":"ok","data":{"[3,5]":"foo"}}"#;
+/// let deser: HashMap<(i32, i32), String> = json_to_map_at(ser, "/data")?;
+///
+/// assert_eq!(deser[&(3, 5)], "foo");
+/// assert!(json_to_map_at::<(i32, i32), String>(ser, "/missing").is_err());
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_at<K,V>(str: &str, pointer: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut v: serde_json::Value = serde_json::from_str(str)?;
+  let located = v.pointer_mut(pointer).ok_or_else(|| serde_json::Error::custom(format_args!("no value at pointer {:?}", pointer)))?;
+  match located.take() {
+    serde_json::Value::Object(o) => crate::map_to_iter(o).collect(),
+    _ => Err(serde_json::Error::custom("Value is not a JSON map"))
+  }
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but also returns the keys in the order in which they appear in the document.
+///
+/// The document is streamed as in [json_to_vec_all()](fn.json_to_vec_all.html), so the order is preserved.
+/// If a key appears more than once, the map holds its last value, and the key is listed once, at its first position.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[7,9]":"bar","[3,5]":"foo"}"#;
+/// let (map, keys): (HashMap<(i32, i32), String>, _) = json_to_map_indexed(ser)?;
+///
+/// assert_eq!(keys, vec![(7, 9), (3, 5)]);
+/// assert_eq!(map[&keys[1]], "foo");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_indexed<K,V>(str: &str) -> Result<(std::collections::HashMap<K,V>, Vec<K>), serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Clone + Any,
+for<'de> V: Deserialize<'de>
+{
+  let entries: Vec<(K,V)> = crate::json_to_vec_all(str)?;
+  let mut map = std::collections::HashMap::with_capacity(entries.len());
+  let mut keys = Vec::with_capacity(entries.len());
+  for (key, val) in entries {
+    if map.insert(key.clone(), val).is_none() {
+      keys.push(key);
     }
   }
+  Ok((map, keys))
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but also returns the parsed `serde_json::Map` with the original string keys.
+///
+/// The document is only parsed once. This is useful for diagnostics, such as logging the raw structure alongside the typed map.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let (deser, raw): (HashMap<(i32, i32), i32>, _) = json_to_map_with_raw(r#"{"[3,5]":7}"#)?;
+/// assert_eq!(deser[&(3, 5)], 7);
+/// assert_eq!(raw["[3,5]"], 7);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn json_to_map_with_raw<K,V>(str: &str) -> Result<(std::collections::HashMap<K,V>, serde_json::Map<String, serde_json::Value>), serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v| { map.insert(k, v); })?;
+  Ok((map, o))
+}
+
+/// Reverses [to_json_map_prefixed()](trait.MapIterToJson.html#method.to_json_map_prefixed), returning a `HashMap<K,V>`.
+///
+/// `prefix` is removed from every key before it is parsed. If any key does not start with `prefix`, an error is returned.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"tenant1:[3,5]":"foo"}"#;
+/// let deser: HashMap<(i32, i32), String> = json_to_map_prefixed(ser, "tenant1:")?;
+///
+/// assert_eq!(deser[&(3, 5)], "foo");
+/// assert!(json_to_map_prefixed::<(i32, i32), String>(ser, "tenant2:").is_err());
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_prefixed<K,V>(str: &str, prefix: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  let key_fn = |key: &str| {
+    let stripped = key.strip_prefix(prefix).ok_or_else(|| crate::serde_with_utils::invalid_key(key, format_args!("missing prefix {:?}", prefix)))?;
+    crate::serde_with_utils::string_to_key(stripped)
+  };
+  crate::serde_with_utils::for_each_entry(&o, key_fn, |k, v| { map.insert(k, v); })?;
   Ok(map)
 }
+
+/// Same as [json_to_map()](fn.json_to_map.html), but only the entries for which `keep` returns `true` are inserted.
+///
+/// Every entry is still deserialized and validated, but discarded entries never occupy the returned map.
+/// This is cheaper than calling `retain()` afterwards when most entries are discarded.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[0,1]":"foo","[1,2]":"bar","[0,3]":"baz"}"#;
+/// let shard: HashMap<(i32, i32), String> = json_to_map_retain(ser, |k: &(i32, i32), _v| k.0 == 0)?;
+///
+/// assert_eq!(shard.len(), 2);
+/// assert!(!shard.contains_key(&(1, 2)));
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_retain<K,V,F>(str: &str, mut keep: F) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>,
+F: FnMut(&K, &V) -> bool
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v| {
+    if keep(&k, &v) {
+      map.insert(k, v);
+    }
+  })?;
+  Ok(map)
+}
+
+/// Reverses [to_json_map_uniform()](trait.MapIterToJson.html#method.to_json_map_uniform), returning a `HashMap<K,V>`.
+///
+/// Every key is parsed with `serde_json::from_str()`, so `String` keys must be quoted JSON strings.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let deser: HashMap<String, i32> = json_to_map_uniform(r#"{"\"foo\"":5}"#)?;
+/// assert_eq!(deser["foo"], 5);
+///
+/// assert!(json_to_map_uniform::<String, i32>(r#"{"foo":5}"#).is_err());
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_uniform<K,V>(str: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  let key_fn = |key: &str| serde_json::from_str(key).map_err(|e| crate::serde_with_utils::invalid_key(key, e));
+  crate::serde_with_utils::for_each_entry(&o, key_fn, |k, v| { map.insert(k, v); })?;
+  Ok(map)
+}
+
+/// Reverses [to_json_map_base64_keys()](trait.MapIterToJson.html#method.to_json_map_base64_keys), returning a `HashMap<K,V>`.
+///
+/// Every key is decoded from standard padded base64, and `K` is deserialized from the resulting sequence of bytes.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let deser: HashMap<Vec<u8>, i32> = json_to_map_base64_keys(r#"{"AP8QIA==":5}"#)?;
+/// assert_eq!(deser[&vec![0, 255, 16, 32]], 5);
+///
+/// assert!(json_to_map_base64_keys::<Vec<u8>, i32>(r#"{"[0,255]":5}"#).is_err());
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_base64_keys<K,V>(str: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::base64_to_key, |k, v| { map.insert(k, v); })?;
+  Ok(map)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but returns an error if the document, or the JSON text of any non-string key,
+/// contains arrays or objects nested more than `max_depth` levels deep.
+///
+/// serde_json already limits parsing to 128 levels, which prevents stack overflows from deeply nested input.
+/// This allows setting a stricter limit for untrusted input; values above 128 have no additional effect.
+/// Note that serializing deeply nested keys is not limited, as the recursion happens in their `Serialize` impls.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[[1]]":"foo"}"#;
+///
+/// let deser: HashMap<Vec<Vec<i32>>, String> = json_to_map_with_recursion_limit(ser, 2)?;
+/// assert_eq!(deser[&vec![vec![1]]], "foo");
+///
+/// let err = json_to_map_with_recursion_limit::<Vec<Vec<i32>>, String>(ser, 1).unwrap_err();
+/// assert_eq!(err.to_string(), "invalid key \"[[1]]\": recursion limit exceeded");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_with_recursion_limit<K,V>(str: &str, max_depth: usize) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  if crate::serde_with_utils::exceeds_depth(str, max_depth) {
+    return Err(serde_json::Error::custom("recursion limit exceeded"));
+  }
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  let key_fn = |key: &str| {
+    if crate::serde_with_utils::key_is_json::<K>() && crate::serde_with_utils::exceeds_depth(key, max_depth) {
+      return Err(crate::serde_with_utils::invalid_key(key, "recursion limit exceeded"));
+    }
+    crate::serde_with_utils::string_to_key(key)
+  };
+  crate::serde_with_utils::for_each_entry(&o, key_fn, |k, v| { map.insert(k, v); })?;
+  Ok(map)
+}
+
+/// Reverses [to_json_map_framed()](trait.MapIterToJson.html#method.to_json_map_framed), reading one length-prefixed map from `reader`.
+///
+/// Exactly the bytes of one frame are consumed, so further frames can be read from the same stream.
+///
+/// # Errors
+/// Returns an I/O error if the stream ends before the number of bytes given by the length prefix.
+/// The prefix is not trusted to size the buffer up front, so a corrupt prefix cannot cause a large allocation on its own.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let mut first = HashMap::<(i32, i32), String>::new();
+/// first.insert((3, 5), "foo".to_string());
+/// let mut second = HashMap::<(i32, i32), String>::new();
+/// second.insert((7, 9), "bar".to_string());
+///
+/// let mut stream = Vec::new();
+/// first.to_json_map_framed(&mut stream)?;
+/// second.to_json_map_framed(&mut stream)?;
+///
+/// let mut reader = stream.as_slice();
+/// assert_eq!(json_to_map_framed::<(i32, i32), String, _>(&mut reader)?, first);
+/// assert_eq!(json_to_map_framed::<(i32, i32), String, _>(&mut reader)?, second);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_framed<K,V,R>(mut reader: R) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>,
+R: std::io::Read
+{
+  let mut len = [0u8; 4];
+  reader.read_exact(&mut len).map_err(serde_json::Error::io)?;
+  let len = u32::from_le_bytes(len) as u64;
+  let mut buf = Vec::new();
+  reader.by_ref().take(len).read_to_end(&mut buf).map_err(serde_json::Error::io)?;
+  if (buf.len() as u64) < len {
+    return Err(serde_json::Error::io(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "frame is shorter than its length prefix")));
+  }
+  let str = std::str::from_utf8(&buf).map_err(serde_json::Error::custom)?;
+  json_to_map(str)
+}
+
+/// Same as [json_to_map()](fn.json_to_map.html), but returns an error if any key is not contained in `allowed`.
+///
+/// Each key is parsed and then checked against the set before it is inserted, so a closed set of keys can be
+/// enforced without a separate validation pass. The error names the first offending key.
+///
+/// # Examples
+/// ```
+/// use std::collections::{HashMap, HashSet};
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let allowed: HashSet<(i32, i32)> = [(3, 5), (7, 9)].into_iter().collect();
+///
+/// let deser: HashMap<(i32, i32), String> = json_to_map_allowed(r#"{"[3,5]":"foo"}"#, &allowed)?;
+/// assert_eq!(deser[&(3, 5)], "foo");
+///
+/// let err = json_to_map_allowed::<(i32, i32), String>(r#"{"[1,2]":"bar"}"#, &allowed).unwrap_err();
+/// assert_eq!(err.to_string(), "invalid key \"[1,2]\": key is not allowed");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_allowed<K,V>(str: &str, allowed: &std::collections::HashSet<K>) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  let key_fn = |key: &str| {
+    let key_obj: K = crate::serde_with_utils::string_to_key(key)?;
+    if !allowed.contains(&key_obj) {
+      return Err(crate::serde_with_utils::invalid_key(key, "key is not allowed"));
+    }
+    Ok(key_obj)
+  };
+  crate::serde_with_utils::for_each_entry(&o, key_fn, |k, v| { map.insert(k, v); })?;
+  Ok(map)
+}
+
+// Below this many entries per thread, spawning a thread costs more than it saves
+#[cfg(feature = "parallel")]
+const PAR_MIN_ENTRIES_PER_THREAD: usize = 512;
+
+/// Same as [json_to_map()](fn.json_to_map.html), but deserializes the keys and values on multiple threads.
+///
+/// Requires the `parallel` feature.
+///
+/// The document is first parsed into its string-keyed entries, which are then split evenly across
+/// up to `std::thread::available_parallelism()` scoped threads. Each thread is given at least 512 entries,
+/// so smaller maps use fewer threads, and maps with fewer than 1024 entries are deserialized on the calling thread.
+/// This only pays off for large maps whose keys or values are expensive to deserialize.
+///
+/// # Errors
+/// If any entries fail, the error of the first failing entry in key order is returned, in the same format as [json_to_map()](fn.json_to_map.html).
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let map: HashMap<(i32, i32), String> = (0..1000).map(|i| ((i, i + 1), i.to_string())).collect();
+///
+/// let ser = map.to_json_map()?;
+/// let deser: HashMap<(i32, i32), String> = par_json_to_map(&ser)?;
+///
+/// assert_eq!(map, deser);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+#[cfg(feature = "parallel")]
+pub fn par_json_to_map<K,V>(str: impl AsRef<str>) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any + Send,
+for<'de> V: Deserialize<'de> + Send
+{
+  let o = crate::serde_with_utils::parse_json_map(str.as_ref())?;
+  let threads = std::thread::available_parallelism().map_or(1, |n| n.get()).min(o.len() / PAR_MIN_ENTRIES_PER_THREAD);
+  if threads <= 1 {
+    let mut map = std::collections::HashMap::with_capacity(o.len());
+    crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v| { map.insert(k, v); })?;
+    return Ok(map);
+  }
+  let entries: Vec<(&String, &serde_json::Value)> = o.iter().collect();
+  // rounded up, so there are at most `threads` chunks
+  let chunk_size = entries.len() / threads + 1;
+  let chunks: Vec<Result<Vec<(K,V)>, serde_json::Error>> = std::thread::scope(|scope| {
+    let handles: Vec<_> = entries.chunks(chunk_size).map(|chunk| scope.spawn(move || {
+      let mut entries = Vec::with_capacity(chunk.len());
+      crate::serde_with_utils::for_each_entry(chunk.iter().copied(), crate::serde_with_utils::string_to_key, |k, v| entries.push((k, v)))?;
+      Ok(entries)
+    })).collect();
+    // a panic while deserializing is resumed on the calling thread, as it would be without threads
+    handles.into_iter().map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+  });
+  let mut map = std::collections::HashMap::with_capacity(entries.len());
+  for chunk in chunks {
+    map.extend(chunk?);
+  }
+  Ok(map)
+}
+
+// Appends the name of V to its deserialization errors, for json_to_map_with_type_names().
+struct WithTypeName<V>(V);
+
+impl<'d,V> Deserialize<'d> for WithTypeName<V> where
+  for<'de> V: Deserialize<'de>
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+    D: serde::de::Deserializer<'d>
+  {
+    V::deserialize(deserializer).map(WithTypeName)
+      .map_err(|e| serde::de::Error::custom(format_args!("{} (value type is `{}`)", e, std::any::type_name::<V>())))
+  }
+}
+
+// Holds None instead of failing when V cannot be deserialized, for json_to_map_with_default().
+// Only used with the serde_json::Value deserializer, which is left in a valid state after an error.
+struct OrDefault<V>(Option<V>);
+
+impl<'d,V> Deserialize<'d> for OrDefault<V> where
+  for<'de> V: Deserialize<'de>
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+    D: serde::de::Deserializer<'d>
+  {
+    Ok(OrDefault(V::deserialize(deserializer).ok()))
+  }
+}
+
+// Parses JSON strings once more before deserializing V from them, for json_to_map_nested_value().
+struct NestedJson<V>(V);
+
+impl<'d,V> Deserialize<'d> for NestedJson<V> where
+  for<'de> V: Deserialize<'de>
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+    D: serde::de::Deserializer<'d>
+  {
+    let val_obj: Result<V, serde_json::Error> = match serde_json::Value::deserialize(deserializer)? {
+      serde_json::Value::String(nested) => serde_json::from_str(&nested),
+      val => V::deserialize(val)
+    };
+    val_obj.map(NestedJson).map_err(serde::de::Error::custom)
+  }
+}
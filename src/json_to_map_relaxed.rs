@@ -0,0 +1,152 @@
+use std::any::Any;
+use std::hash::Hash;
+use serde::de::Deserialize;
+
+/// Same as [json_to_map()](fn.json_to_map.html), but tolerates `//` and `/* */` comments and trailing commas.
+///
+/// The document is normalized to strict JSON first, and then parsed exactly as [json_to_map()](fn.json_to_map.html) would.
+/// Comment-like sequences inside of JSON strings are left untouched.
+///
+/// Requires the `relaxed` feature.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{
+///   // the answer
+///   "42": "foo", /* trailing comma below */
+///   "7": "bar",
+/// }"#;
+/// let deser: HashMap<i32, String> = json_to_map_relaxed(ser)?;
+///
+/// assert_eq!(deser[&42], "foo");
+/// assert_eq!(deser[&7], "bar");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_relaxed<K,V>(str: &str) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + std::cmp::Eq + Hash + Any,
+for<'de> V: Deserialize<'de>
+{
+  crate::json_to_map(strip_trailing_commas(&strip_comments(str)))
+}
+
+// Replaces each comment with a single space so that adjacent tokens are not merged.
+// Unterminated block comments are left in place so that serde_json reports the error.
+fn strip_comments(str: &str) -> String {
+  let bytes = str.as_bytes();
+  let mut out = String::with_capacity(str.len());
+  let mut i = 0;
+  let mut start = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'"' => { i = skip_string(bytes, i); },
+      b'/' if bytes.get(i + 1) == Some(&b'/') => {
+        out.push_str(&str[start..i]);
+        out.push(' ');
+        while i < bytes.len() && bytes[i] != b'\n' { i += 1; }
+        start = i;
+      },
+      b'/' if bytes.get(i + 1) == Some(&b'*') => {
+        match str[i + 2..].find("*/") {
+          Some(end) => {
+            out.push_str(&str[start..i]);
+            out.push(' ');
+            i = i + 2 + end + 2;
+            start = i;
+          },
+          None => { i = bytes.len(); }
+        }
+      },
+      _ => { i += 1; }
+    }
+  }
+  out.push_str(&str[start..]);
+  out
+}
+
+// Removes any comma which is followed only by whitespace before a closing `}` or `]`.
+fn strip_trailing_commas(str: &str) -> String {
+  let bytes = str.as_bytes();
+  let mut out = String::with_capacity(str.len());
+  let mut i = 0;
+  let mut start = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'"' => { i = skip_string(bytes, i); },
+      b',' => {
+        let next = bytes[i + 1..].iter().find(|b| !b.is_ascii_whitespace());
+        if matches!(next, Some(b'}') | Some(b']')) {
+          out.push_str(&str[start..i]);
+          start = i + 1;
+        }
+        i += 1;
+      },
+      _ => { i += 1; }
+    }
+  }
+  out.push_str(&str[start..]);
+  out
+}
+
+// Given the index of an opening quote, returns the index just past the closing quote.
+fn skip_string(bytes: &[u8], open: usize) -> usize {
+  let mut i = open + 1;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'\\' => { i += 2; },
+      b'"' => { return i + 1; },
+      _ => { i += 1; }
+    }
+  }
+  bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::*;
+  use std::collections::HashMap;
+  use serde::{Serialize, Deserialize};
+
+  #[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+  struct Test {
+    pub a: i32,
+    pub b: i32
+  }
+
+  #[test]
+  fn test_relaxed_struct_map() {
+    let ser = "{\n  // comment\n  \"{\\\"a\\\":3,\\\"b\\\":5}\": {\"a\":7,\"b\":9,}, /* block */\n}";
+    let deser: HashMap<Test, Test> = json_to_map_relaxed(ser).unwrap();
+    let mut expected = HashMap::new();
+    expected.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    assert_eq!(deser, expected);
+  }
+
+  #[test]
+  fn test_relaxed_preserves_strings() {
+    let ser = r#"{"// not a comment": "/* nor this */", "trailing,}": "\",]",}"#;
+    let deser: HashMap<String, String> = json_to_map_relaxed(ser).unwrap();
+    assert_eq!(deser["// not a comment"], "/* nor this */");
+    assert_eq!(deser["trailing,}"], "\",]");
+  }
+
+  #[test]
+  fn test_relaxed_strict_input() {
+    let mut data = HashMap::<i32, Test>::new();
+    data.insert(5, Test {a: 6, b: 7});
+    let ser = data.to_json_map().unwrap();
+    let deser: HashMap<i32, Test> = json_to_map_relaxed(&ser).unwrap();
+    assert_eq!(data, deser);
+  }
+
+  #[test]
+  fn test_relaxed_unterminated_comment() {
+    let res: Result<HashMap<i32, i32>, _> = json_to_map_relaxed("{\"5\": 6 /* oops }");
+    assert!(res.is_err());
+  }
+}
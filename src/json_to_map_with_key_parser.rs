@@ -0,0 +1,47 @@
+use std::fmt::Display;
+use std::hash::Hash;
+use serde::de::Deserialize;
+
+/// Same as [json_to_map()](fn.json_to_map.html), but each key is converted by the provided function instead of `serde_json::from_str()`.
+///
+/// This allows reading documents where the keys were produced by a different format (such as RON or TOML),
+/// while the values are still regular JSON. Errors returned by the parser are converted with `serde_json::Error::custom()`,
+/// prefixed with the offending key.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde::{Serialize, Deserialize};
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+/// pub struct Test {
+///   pub a: i32,
+///   pub b: i32
+/// }
+///
+/// fn try_main() -> Result<(), Error> {
+/// // Keys were written as "a;b" by some other tool
+/// let ser = r#"{"3;5":{"a":7,"b":9}}"#;
+/// let deser: HashMap<Test, Test> = json_to_map_with_key_parser(ser, |key| {
+///   let (a, b) = key.split_once(';').ok_or("missing separator")?;
+///   Ok::<Test, Box<dyn std::error::Error>>(Test { a: a.parse()?, b: b.parse()? })
+/// })?;
+///
+/// assert_eq!(deser[&Test {a: 3, b: 5}], Test {a: 7, b: 9});
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_map_with_key_parser<K,V,F,E>(str: &str, mut parse: F) -> Result<std::collections::HashMap<K,V>, serde_json::Error> where
+K: std::cmp::Eq + Hash,
+for<'de> V: Deserialize<'de>,
+F: FnMut(&str) -> Result<K,E>,
+E: Display
+{
+  let mut map: std::collections::HashMap<K,V> = std::collections::HashMap::new();
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  let key_fn = |key: &str| parse(key).map_err(|e| crate::serde_with_utils::invalid_key(key, e));
+  crate::serde_with_utils::for_each_entry(&o, key_fn, |k, v| { map.insert(k, v); })?;
+  Ok(map)
+}
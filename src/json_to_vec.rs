@@ -1,10 +1,14 @@
 
-use std::any::{Any, TypeId};
-use serde::ser::Error;
+use std::any::Any;
 use serde::de::Deserialize;
 
 /// Reverses to_json_map(), returning a `Vec<(K,V)>`.
 ///
+/// The input may be any string type, such as `&str`, `String`, or `Cow<str>`.
+///
+/// The document is first parsed into a `serde_json::Map`, so duplicate keys are collapsed (the last one wins),
+/// and entries are not returned in document order. Use [json_to_vec_all()](fn.json_to_vec_all.html) to keep every entry.
+///
 /// # Examples
 /// ```
 /// use serde::{Serialize, Deserialize};
@@ -27,27 +31,131 @@ use serde::de::Deserialize;
 /// Ok(()) }
 /// try_main().unwrap();
 /// ```
-pub fn json_to_vec<'a,K,V>(str: &'a str) -> Result<Vec<(K,V)>, serde_json::Error> where
+pub fn json_to_vec<K,V>(str: impl AsRef<str>) -> Result<Vec<(K,V)>, serde_json::Error> where
 for<'de> K: Deserialize<'de> + Any,
 for<'de> V: Deserialize<'de>
 {
   let mut vec: Vec<(K,V)> = vec![];
-  let v: serde_json::Value = serde_json::from_str(&str)?;
-  let o = v.as_object().ok_or(serde_json::Error::custom("Value is not a JSON map"))?;
-  // handle strings specially as they are not objects
-  // compiler seems to be able to optimize this branch away statically
-  if TypeId::of::<K>() == TypeId::of::<String>() {
-    for (key, val) in o.iter() {
-      let key_obj: K = <K as Deserialize>::deserialize(serde_json::Value::from(key.as_str()))?;
-      let val_obj: V = <V as Deserialize>::deserialize(val)?;
-      vec.push((key_obj, val_obj));
-    }
-  } else {
-    for (key, val) in o.iter() {
-      let key_obj: K = serde_json::from_str(key)?;
-      let val_obj: V = <V as Deserialize>::deserialize(val)?;
-      vec.push((key_obj, val_obj));
-    }
-  }
+  let o = crate::serde_with_utils::parse_json_map(str.as_ref())?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_key, |k, v| vec.push((k, v)))?;
+  Ok(vec)
+}
+
+/// Reverses [to_json_map_float_keys()](trait.MapIterToJson.html#method.to_json_map_float_keys), returning a `Vec<(K,V)>`.
+///
+/// Each key is parsed as a decimal number with any number of decimals, so `"1.5"` and `"1.50"` are both read as `1.5`,
+/// and `K` is deserialized from it, so it may be `f64`, `f32`, or a wrapper type which deserializes from a float.
+/// As for [json_to_vec()](fn.json_to_vec.html), the entries are ordered by their key strings.
+///
+/// # Examples
+/// ```
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let deser: Vec<(f64, String)> = json_to_vec_float_keys(r#"{"1.50":"foo"," 2":"bar"}"#)?;
+/// assert_eq!(deser, vec![(2.0, "bar".to_string()), (1.5, "foo".to_string())]);
+///
+/// assert!(json_to_vec_float_keys::<f64, String>(r#"{"NaN":"foo"}"#).is_err());
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_vec_float_keys<K,V>(str: &str) -> Result<Vec<(K,V)>, serde_json::Error> where
+for<'de> K: Deserialize<'de>,
+for<'de> V: Deserialize<'de>
+{
+  let mut vec: Vec<(K,V)> = vec![];
+  let o = crate::serde_with_utils::parse_json_map(str)?;
+  crate::serde_with_utils::for_each_entry(&o, crate::serde_with_utils::string_to_float_key, |k, v| vec.push((k, v)))?;
+  Ok(vec)
+}
+
+/// Same as [json_to_vec()](fn.json_to_vec.html), but the entries are sorted by the native `K: Ord` ordering.
+///
+/// The result is suitable for lookups with `binary_search_by()`, as a compact read-only alternative to a `HashMap`.
+///
+/// # Examples
+/// ```
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"10":"c","1":"a","2":"b"}"#;
+/// let table: Vec<(i32, String)> = json_to_sorted_vec(ser)?;
+///
+/// assert_eq!(table, vec![(1, "a".to_string()), (2, "b".to_string()), (10, "c".to_string())]);
+/// let idx = table.binary_search_by(|(k, _)| k.cmp(&10)).unwrap();
+/// assert_eq!(table[idx].1, "c");
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_sorted_vec<K,V>(str: &str) -> Result<Vec<(K,V)>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + Ord + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut vec: Vec<(K,V)> = json_to_vec(str)?;
+  vec.sort_by(|a, b| a.0.cmp(&b.0));
   Ok(vec)
 }
+
+/// Reverses to_json_map(), returning a `Vec<(K,V)>` that contains every entry of the document in its original order.
+///
+/// Unlike [json_to_vec()](fn.json_to_vec.html), the document is read with a streaming `serde_json::Deserializer`
+/// instead of going through a `serde_json::Map`, so duplicate keys are preserved rather than collapsed.
+/// Errors include the zero-based index of the failing entry, as in `at entry 3`.
+///
+/// # Examples
+/// ```
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"5":"created","3":"opened","5":"deleted"}"#;
+///
+/// let all: Vec<(i32,String)> = json_to_vec_all(ser)?;
+/// assert_eq!(all, vec![(5, "created".to_string()), (3, "opened".to_string()), (5, "deleted".to_string())]);
+///
+/// // json_to_vec() only keeps the last value for each key
+/// let collapsed: Vec<(i32,String)> = json_to_vec(ser)?;
+/// assert_eq!(collapsed.len(), 2);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_vec_all<K,V>(str: &str) -> Result<Vec<(K,V)>, serde_json::Error> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut deserializer = serde_json::Deserializer::from_str(str);
+  let vec = crate::serde_with_utils::deserialize(&mut deserializer)?;
+  deserializer.end()?;
+  Ok(vec)
+}
+
+/// Reverses [slices_to_json_map()](fn.slices_to_json_map.html), returning the keys and values as two parallel `Vec`s.
+///
+/// Like [json_to_vec_all()](fn.json_to_vec_all.html), the document is streamed, so the entries are kept in document order
+/// (including duplicate keys), and no intermediate `Vec<(K,V)>` is created.
+///
+/// # Examples
+/// ```
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let ser = r#"{"[7,9]":"bar","[3,5]":"foo"}"#;
+/// let (keys, values): (Vec<(i32, i32)>, Vec<String>) = json_to_slices(ser)?;
+///
+/// assert_eq!(keys, vec![(7, 9), (3, 5)]);
+/// assert_eq!(values, vec!["bar", "foo"]);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn json_to_slices<K,V>(str: &str) -> Result<(Vec<K>, Vec<V>), serde_json::Error> where
+for<'de> K: Deserialize<'de> + Any,
+for<'de> V: Deserialize<'de>
+{
+  let mut deserializer = serde_json::Deserializer::from_str(str);
+  let columns = crate::serde_with_utils::deserialize(&mut deserializer)?;
+  deserializer.end()?;
+  Ok(columns)
+}
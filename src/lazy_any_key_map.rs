@@ -0,0 +1,99 @@
+use std::any::Any;
+use std::marker::PhantomData;
+use serde::ser::Serialize;
+use serde::de::{Deserialize, Deserializer};
+
+/// A JSON map written by to_json_map(), whose entries are only deserialized when they are accessed.
+///
+/// Deserializing a `LazyAnyKeyMap` only parses the document into a `serde_json::Map` with the original string keys.
+/// Each call to [get()](#method.get) then converts the requested key to its string form, exactly as
+/// [to_json_map()](trait.MapIterToJson.html#method.to_json_map) would, and deserializes only the matching value.
+/// This saves work when only a few entries of a large map are needed.
+///
+/// Keys are matched by their exact string form, so documents which were not written by this crate
+/// (for example with extra whitespace inside the keys) may not find every entry.
+///
+/// # Examples
+/// ```
+/// use serde::Deserialize;
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// #[derive(Deserialize)]
+/// pub struct Document {
+///   pub routes: LazyAnyKeyMap<(i32, i32), String>
+/// }
+///
+/// fn try_main() -> Result<(), Error> {
+/// let doc: Document = serde_json::from_str(r#"{"routes":{"[3,5]":"foo","[7,9]":"bar"}}"#)?;
+///
+/// assert_eq!(doc.routes.get(&(3, 5)).unwrap()?, "foo");
+/// assert!(doc.routes.get(&(1, 1)).is_none());
+/// assert_eq!(doc.routes.keys().collect::<Result<Vec<_>, _>>()?, vec![(3, 5), (7, 9)]);
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub struct LazyAnyKeyMap<K,V> {
+  map: serde_json::Map<String, serde_json::Value>,
+  kv: PhantomData<(K,V)>
+}
+
+impl<K,V> LazyAnyKeyMap<K,V> {
+  /// Wraps an already parsed `serde_json::Map`, such as one returned by [json_to_map_with_raw()](fn.json_to_map_with_raw.html).
+  pub fn new(map: serde_json::Map<String, serde_json::Value>) -> Self {
+    LazyAnyKeyMap { map, kv: PhantomData }
+  }
+
+  /// Returns the number of entries, without deserializing any of them.
+  pub fn len(&self) -> usize {
+    self.map.len()
+  }
+
+  /// Returns true if the map has no entries.
+  pub fn is_empty(&self) -> bool {
+    self.map.is_empty()
+  }
+
+  /// Returns the underlying `serde_json::Map` with the original string keys.
+  pub fn into_inner(self) -> serde_json::Map<String, serde_json::Value> {
+    self.map
+  }
+
+  /// Deserializes the value for `key`, or returns `None` if the map has no such entry.
+  ///
+  /// An error is returned if `key` cannot be converted to a string, or if the value cannot be deserialized into `V`.
+  /// Values are not cached, so each call deserializes the value again.
+  pub fn get(&self, key: &K) -> Option<Result<V, serde_json::Error>> where
+  K: Serialize + Any,
+  for<'de> V: Deserialize<'de>
+  {
+    let key_string = match crate::serde_with_utils::key_to_string(key) {
+      Ok(key_string) => key_string,
+      Err(e) => { return Some(Err(e)); }
+    };
+    let val = self.map.get(key_string.as_ref())?;
+    Some(<V as Deserialize>::deserialize(val).map_err(|e| crate::serde_with_utils::invalid_value(&key_string, e)))
+  }
+
+  /// Returns true if the map has an entry for `key`, without deserializing its value.
+  pub fn contains_key(&self, key: &K) -> Result<bool, serde_json::Error> where
+  K: Serialize + Any
+  {
+    Ok(self.map.contains_key(crate::serde_with_utils::key_to_string(key)?.as_ref()))
+  }
+
+  /// Deserializes each key, in the order of their string forms, without deserializing the values.
+  pub fn keys(&self) -> impl Iterator<Item = Result<K, serde_json::Error>> + '_ where
+  for<'de> K: Deserialize<'de> + Any
+  {
+    self.map.keys().map(|key| crate::serde_with_utils::string_to_key(key.as_str()))
+  }
+}
+
+impl<'de,K,V> Deserialize<'de> for LazyAnyKeyMap<K,V> {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+    D: Deserializer<'de>
+  {
+    serde_json::Map::deserialize(deserializer).map(LazyAnyKeyMap::new)
+  }
+}
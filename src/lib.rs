@@ -9,6 +9,26 @@
 //! De/serialization of structs with nested maps is supported via the following attributes:  
 //! [#[serde(with = "any_key_map")]](any_key_map/index.html)  
 //! [#[serde(with = "any_key_vec")]](any_key_vec/index.html)
+//!
+//! Note that the key type is not recorded in the output. Different keys may serialize to the same JSON map key,
+//! for example the `bool` key `true` and the `String` key `"true"` both become `"true"`, as do the `i32` key `5` and the `String` key `"5"`.
+//! Such keys can only be told apart by the type that is requested during deserialization.
+//! `Option` keys are written as `"null"` for `None` and as the inner key for `Some`, so nested options such as `Some(None)` are read back as `None`.
+//!
+//! Keys are always converted with serde_json, whose serializer and deserializer report `is_human_readable() == true`.
+//! This holds even when a map is nested inside a non-human-readable format via `#[serde(with = "any_key_map")]`,
+//! so types that change their representation based on that flag will always use their human-readable form as a key.
+//!
+//! `serde_json::Number` keys are written with the same digits that serde_json would write for the number itself,
+//! so they round-trip exactly. With the `arbitrary_precision` feature this extends to integers and decimals of any length.
+//!
+//! `f32` and `f64` keys must be finite, as serde_json writes NaN and infinities as `null`, so serializing them returns an error.
+//! Wrapper types such as `ordered_float::OrderedFloat` are written like the float they contain, and finite values round-trip exactly.
+//! A NaN inside such a wrapper is still written as `null`, and reading it back fails with an `invalid key` error.
+//!
+//! Parsing is limited by serde_json to 128 levels of nested arrays and objects, both for the document and for each key,
+//! and deeper input returns an error instead of overflowing the stack. A stricter limit can be set with
+//! [json_to_map_with_recursion_limit()](fn.json_to_map_with_recursion_limit.html).
 //! ```
 //! use std::collections::HashMap;
 //! use serde::{Serialize, Deserialize};
@@ -75,19 +95,44 @@
 
 // modules
 mod json_to_map;
+mod from_json_map;
+#[cfg(feature = "relaxed")]
+mod json_to_map_relaxed;
+mod json_to_map_with_key_parser;
 mod json_to_vec;
 mod json_to_iter;
+mod json_map_lines;
 mod map_iter_to_json;
+mod ref_key_map_iter_to_json;
 mod vec_iter_to_json;
 mod consuming_iter_to_json;
+mod guard_iter_to_json;
+mod slices_to_json;
+mod lazy_any_key_map;
+mod json_map_writer;
+mod json_map_reader;
 mod serde_with_utils;
 
 // exports
-pub use json_to_map::json_to_map;
-pub use json_to_vec::json_to_vec;
-pub use json_to_iter::json_to_iter;
-pub use map_iter_to_json::MapIterToJson;
-pub use vec_iter_to_json::VecIterToJson;
-pub use consuming_iter_to_json::ConsumingIterToJson;
+pub use json_to_map::{json_to_map, json_to_btreemap, json_to_map_with_type_names, json_to_arc_map, json_to_map_with_default, json_to_map_with, json_to_map_nested_value, json_to_map_at, json_to_map_indexed, json_to_map_with_raw, json_to_map_prefixed, json_to_map_retain, json_to_map_uniform, json_to_map_base64_keys, json_to_map_with_recursion_limit, json_to_map_framed, json_to_map_allowed, reload_map_from_json, merge_json_maps, json_to_map_with_meta, json_to_map_strict_numeric};
+#[cfg(feature = "relaxed")]
+pub use json_to_map_relaxed::json_to_map_relaxed;
+#[cfg(feature = "parallel")]
+pub use json_to_map::par_json_to_map;
+pub use from_json_map::{FromJsonMap, FromJsonMapStr, TryFromJsonMap};
+pub use json_to_map_with_key_parser::json_to_map_with_key_parser;
+pub use json_to_vec::{json_to_vec, json_to_vec_all, json_to_vec_float_keys, json_to_sorted_vec, json_to_slices};
+pub use json_to_iter::{json_to_iter, json_to_iter_lossy, json_to_iter_map, json_to_vec_lossy, json_to_collection, json_to_collection_bounded, json_for_each, map_to_iter};
+pub use json_map_lines::json_map_lines;
+pub use map_iter_to_json::{MapIterToJson, serialize_map_into};
+pub use ref_key_map_iter_to_json::RefKeyMapIterToJson;
+pub use vec_iter_to_json::{VecIterToJson, serialize_vec_into};
+pub use consuming_iter_to_json::{ConsumingIterToJson, SerializeConsumingIterWrapper, TryIntoJsonMap, try_iter_to_json_map, serialize_consuming_into};
+pub use guard_iter_to_json::guards_to_json_map;
+pub use slices_to_json::slices_to_json_map;
+pub use lazy_any_key_map::LazyAnyKeyMap;
+pub use json_map_writer::JsonMapWriter;
+pub use json_map_reader::{JsonMapReader, json_to_iter_streaming};
+pub use serde_with_utils::{MapIter, serialize_map_entries};
 pub mod any_key_map;
 pub mod any_key_vec;
@@ -1,5 +1,6 @@
 
 use std::any::{Any, TypeId};
+use std::borrow::Cow;
 use std::cell::RefCell;
 use serde::ser::{Serialize, Serializer, SerializeMap, Error};
 
@@ -7,7 +8,7 @@ use serde::ser::{Serialize, Serializer, SerializeMap, Error};
 pub trait MapIterToJson<'a,K,V>: IntoIterator<Item=(&'a K,&'a V)> where
 Self: Sized,
 K: 'a + Serialize + Any,
-V: 'a + Serialize,
+V: 'a + Serialize + ?Sized,
 <Self as IntoIterator>::IntoIter: 'a
 {
   /// Serialize any `IntoIterator<(&K,&V)>` to a JSON map. This includes, but is not limited to, the following example types:  
@@ -17,6 +18,9 @@ V: 'a + Serialize,
   /// return type of `BTreeMap<K,V>::iter()`
   /// 
   /// To create the JSON map keys, `serde_json::to_string()` will be called on each K element.
+  ///
+  /// Entries are written in iteration order. For a `BTreeMap` the output is therefore deterministic and ordered by `K: Ord`,
+  /// which may differ from the lexical order of the stringified keys (for example, `9` sorts before `10`).
   /// 
   /// **This does not consume self**, and is not compatible with consuming iterators, such as those returned by the common
   /// `std::collections::Type::into_iter()` function. For those consuming iterators, call [into_json_map()](trait.ConsumingIterToJson.html#method.into_json_map) instead.
@@ -60,49 +64,674 @@ V: 'a + Serialize,
       iter: RefCell::new(self.into_iter())
     })
   }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the output buffer is preallocated with `capacity` bytes.
+  ///
+  /// For large collections this avoids repeatedly growing the buffer during serialization.
+  /// The capacity can be computed exactly with [json_map_len()](#method.json_map_len).
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<i32, i32>::new();
+  /// map.insert(5, 6);
+  /// let ser = map.to_json_map_with_capacity(1024).unwrap();
+  ///
+  /// assert_eq!(ser, "{\"5\":6}");
+  /// assert!(ser.capacity() >= 1024);
+  /// ```
+  fn to_json_map_with_capacity(self, capacity: usize) -> Result<String, serde_json::Error> {
+    let mut buf = Vec::with_capacity(capacity);
+    serde_json::to_writer(&mut buf, &SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter())
+    })?;
+    // serde_json only writes valid UTF-8
+    String::from_utf8(buf).map_err(serde_json::Error::custom)
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but returns a `Cow` which avoids allocating where possible.
+  ///
+  /// An empty collection returns a borrowed `"{}"` without allocating.
+  /// When `K` is `String`, the keys need no conversion, so the whole map is handed to `serde_json` as it would be for a plain
+  /// `HashMap<String,V>`, without checking each key. Otherwise the output is the same as for [to_json_map()](#method.to_json_map).
+  ///
+  /// # Examples
+  /// ```
+  /// use std::borrow::Cow;
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<String, String>::new();
+  /// assert!(matches!(map.to_json_map_cow().unwrap(), Cow::Borrowed("{}")));
+  ///
+  /// map.insert("foo".to_string(), "bar".to_string());
+  /// assert_eq!(map.to_json_map_cow().unwrap(), serde_json::to_string(&map).unwrap());
+  /// ```
+  fn to_json_map_cow(self) -> Result<Cow<'static, str>, serde_json::Error> {
+    let mut iter = self.into_iter().peekable();
+    if iter.peek().is_none() {
+      return Ok(Cow::Borrowed("{}"));
+    }
+    // compiler seems to be able to optimize this branch away statically
+    if TypeId::of::<K>() == TypeId::of::<String>() {
+      return serde_json::to_string(&SerializeStringKeyMapWrapper {
+        iter: RefCell::new(iter)
+      }).map(Cow::Owned);
+    }
+    serde_json::to_string(&SerializeMapIterWrapper {
+      iter: RefCell::new(iter)
+    }).map(Cow::Owned)
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but only the entries for which `keep` returns `true` are serialized.
+  ///
+  /// Entries are filtered lazily as they are serialized, so no filtered copy of the collection is created.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<i32, i32>::new();
+  /// map.insert(1, 0);
+  /// map.insert(2, 5);
+  /// let ser = map.to_json_map_filtered(|_k, v| *v != 0).unwrap();
+  ///
+  /// assert_eq!(ser, "{\"2\":5}");
+  /// ```
+  fn to_json_map_filtered<F>(self, mut keep: F) -> Result<String, serde_json::Error> where
+  F: FnMut(&K, &V) -> bool
+  {
+    serde_json::to_string(&SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter().filter(move |(k, v)| keep(k, v)))
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the entries are written sorted by the native `K: Ord` ordering.
+  ///
+  /// For a `HashMap`, this produces exactly the output that a `BTreeMap` with the same entries would,
+  /// which is useful for golden tests and stable diffs. References to the entries are buffered and sorted before serialization,
+  /// so numeric keys are written as `"1","2","10"` rather than in the lexical order of their strings.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::{BTreeMap, HashMap};
+  /// use serde_json_any_key::*;
+  ///
+  /// let map: HashMap<i32, &str> = [(10, "c"), (1, "a"), (2, "b")].into_iter().collect();
+  /// let btree: BTreeMap<i32, &str> = map.clone().into_iter().collect();
+  /// let ser = map.to_json_map_sorted_native().unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"1":"a","2":"b","10":"c"}"#);
+  /// assert_eq!(ser, btree.to_json_map().unwrap());
+  /// ```
+  fn to_json_map_sorted_native(self) -> Result<String, serde_json::Error> where
+  K: Ord
+  {
+    self.to_json_map_sorted_by_key(|a, b| a.cmp(b))
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the entries are written in the order given by the comparator `compare`.
+  ///
+  /// References to the entries are buffered and sorted before serialization, which allows overriding
+  /// the natural order of a collection such as a `BTreeMap`. Entries which compare equal keep their relative order.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::BTreeMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = BTreeMap::<i32, &str>::new();
+  /// map.insert(1, "a");
+  /// map.insert(2, "b");
+  /// let ser = map.to_json_map_sorted_by_key(|a, b| b.cmp(a)).unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"2":"b","1":"a"}"#);
+  /// ```
+  fn to_json_map_sorted_by_key<F>(self, mut compare: F) -> Result<String, serde_json::Error> where
+  F: FnMut(&K, &K) -> std::cmp::Ordering
+  {
+    let mut entries: Vec<(&'a K, &'a V)> = self.into_iter().collect();
+    entries.sort_by(|a, b| compare(a.0, b.0));
+    serde_json::to_string(&SerializeMapIterWrapper {
+      iter: RefCell::new(entries.into_iter())
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but produces a canonical form, so that equal collections are always written
+  /// as byte-identical output, for example to be hashed or signed.
+  ///
+  /// The canonicalization is as follows:
+  /// 1. Each key is converted to a string as in [to_json_map()](#method.to_json_map), and each value is converted to a `serde_json::Value`.
+  /// 2. The entries are sorted by their key strings, compared lexicographically by their UTF-8 bytes.
+  ///    The keys of objects nested within the values are sorted the same way, so struct fields are not written in declaration order.
+  /// 3. The result is written without any whitespace. Strings escape only `"`, `\` and control characters, and other characters are written as UTF-8.
+  /// 4. Integers are written without a fraction or exponent, and floats in the shortest form which parses back to the same value,
+  ///    using an exponent for very large or small magnitudes (such as `1e+300`).
+  ///
+  /// This is close to, but not the same as, RFC 8785, which sorts keys by UTF-16 code units and formats numbers as ECMAScript does.
+  ///
+  /// # Errors
+  /// Returns an error if two keys are converted to the same string, as the output would otherwise depend on the iteration order.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde::Serialize;
+  /// use serde_json_any_key::*;
+  ///
+  /// #[derive(Serialize, PartialEq, Eq, Hash)]
+  /// pub struct Test {
+  ///   pub b: i32,
+  ///   pub a: i32
+  /// }
+  ///
+  /// let mut map = HashMap::<i32, Test>::new();
+  /// map.insert(10, Test {b: 1, a: 2});
+  /// map.insert(9, Test {b: 3, a: 4});
+  /// let ser = map.to_json_map_canonical().unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"10":{"a":2,"b":1},"9":{"a":4,"b":3}}"#);
+  /// ```
+  fn to_json_map_canonical(self) -> Result<String, serde_json::Error> {
+    let mut entries = vec![];
+    for (k, v) in self {
+      let key_string = crate::serde_with_utils::key_to_string(k)?.into_owned();
+      entries.push((key_string, crate::serde_with_utils::canonical_value(serde_json::to_value(v)?)));
+    }
+    entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    if let Some(w) = entries.windows(2).find(|w| w[0].0 == w[1].0) {
+      return Err(serde_json::Error::custom(format_args!("duplicate key {:?}", w[0].0)));
+    }
+    serde_json::to_string(&serde_json::Value::Object(entries.into_iter().collect()))
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but each value is passed through `f` and the result is serialized in its place.
+  ///
+  /// The keys are unchanged. This can be used to redact or convert values without modifying or copying the collection.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<i32, String>::new();
+  /// map.insert(5, "hunter2".to_string());
+  /// let ser = map.to_json_map_map_values(|v| "*".repeat(v.len())).unwrap();
+  ///
+  /// assert_eq!(ser, "{\"5\":\"*******\"}");
+  /// ```
+  fn to_json_map_map_values<F,W>(self, mut f: F) -> Result<String, serde_json::Error> where
+  F: FnMut(&'a V) -> W,
+  W: Serialize
+  {
+    serde_json::to_string(&SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter().map(move |(k, v)| (k, f(v))))
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the output is followed by a newline.
+  ///
+  /// Since the map is always written on a single line, the results can be appended to a JSON Lines log
+  /// and read back with [json_map_lines()](fn.json_map_lines.html).
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<i32, String>::new();
+  /// map.insert(5, "a\nb".to_string());
+  /// let ser = map.to_json_map_line().unwrap();
+  ///
+  /// assert_eq!(ser, "{\"5\":\"a\\nb\"}\n");
+  /// ```
+  fn to_json_map_line(self) -> Result<String, serde_json::Error> {
+    let mut buf = self.to_json_map()?;
+    buf.push('\n');
+    Ok(buf)
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but `prefix` is prepended to every stringified key.
+  ///
+  /// This can be used to namespace the entries of several collections within one JSON object.
+  /// The prefix is removed again by [json_to_map_prefixed()](fn.json_to_map_prefixed.html).
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<(i32, i32), i32>::new();
+  /// map.insert((3, 5), 6);
+  /// let ser = map.to_json_map_prefixed("tenant1:").unwrap();
+  ///
+  /// assert_eq!(ser, "{\"tenant1:[3,5]\":6}");
+  /// ```
+  fn to_json_map_prefixed(self, prefix: &str) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&SerializeMapIterWithKeyWrapper {
+      iter: RefCell::new(self.into_iter()),
+      key: |k: &K| Ok(format!("{}{}", prefix, crate::serde_with_utils::key_to_string(k)?))
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but every key is converted with `serde_json::to_string()`,
+  /// including `String`, `PathBuf`, `OsString`, and `std::net` address keys, which are otherwise written as plain strings.
+  ///
+  /// This gives a uniform encoding where every JSON map key is itself a JSON document, so a `String` key `foo`
+  /// becomes `"\"foo\""`. The output can be read back with [json_to_map_uniform()](fn.json_to_map_uniform.html).
+  ///
+  /// For other key types the output is identical to [to_json_map()](#method.to_json_map).
+  /// This is also the path to use when every key should go through `serde_json::to_string()` unconditionally,
+  /// with no special cases for string-like key types.
+  ///
+  /// # Errors
+  /// Returns an error if a float key is not finite, as `serde_json` would write it as `null`.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<String, i32>::new();
+  /// map.insert("foo".to_string(), 5);
+  ///
+  /// assert_eq!(map.to_json_map().unwrap(), r#"{"foo":5}"#);
+  /// assert_eq!(map.to_json_map_uniform().unwrap(), r#"{"\"foo\"":5}"#);
+  /// ```
+  fn to_json_map_uniform(self) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&SerializeMapIterWithKeyWrapper {
+      iter: RefCell::new(self.into_iter()),
+      key: |k: &K| crate::serde_with_utils::json_key_to_string(k)
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but every key must serialize as a sequence of bytes, such as `Vec<u8>` or `[u8; N]`,
+  /// and is written as standard padded base64 (RFC 4648) instead of a JSON array.
+  ///
+  /// This is much more compact for binary keys. Values are written as usual.
+  /// The output can be read back with [json_to_map_base64_keys()](fn.json_to_map_base64_keys.html).
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<Vec<u8>, i32>::new();
+  /// map.insert(vec![0, 255, 16, 32], 5);
+  ///
+  /// assert_eq!(map.to_json_map().unwrap(), r#"{"[0,255,16,32]":5}"#);
+  /// assert_eq!(map.to_json_map_base64_keys().unwrap(), r#"{"AP8QIA==":5}"#);
+  /// ```
+  fn to_json_map_base64_keys(self) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&SerializeMapIterWithKeyWrapper {
+      iter: RefCell::new(self.into_iter()),
+      key: |k: &K| crate::serde_with_utils::key_to_base64(k)
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but every key must serialize as a JSON number, such as `f64`,
+  /// and is written with exactly `precision` decimals, for example `"1.50"`.
+  ///
+  /// **This is lossy**: keys are rounded to the given precision, so distinct keys may be written identically.
+  /// In exchange, the output does not depend on the exact bits of each key, which makes bucketed measurements reproducible.
+  /// The output can be read back with [json_to_vec_float_keys()](fn.json_to_vec_float_keys.html).
+  ///
+  /// # Errors
+  /// Returns an error if a key is not a number, or is not finite.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::BTreeMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = BTreeMap::<i32, &str>::new();
+  /// map.insert(-2, "a");
+  /// assert_eq!(map.to_json_map_float_keys(2).unwrap(), r#"{"-2.00":"a"}"#);
+  ///
+  /// let vec = vec![(1.5f64, "b"), (0.1 + 0.2, "c")];
+  /// assert_eq!(vec.to_json_map_float_keys(2).unwrap(), r#"{"1.50":"b","0.30":"c"}"#);
+  /// ```
+  fn to_json_map_float_keys(self, precision: usize) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&SerializeMapIterWithKeyWrapper {
+      iter: RefCell::new(self.into_iter()),
+      key: |k: &K| crate::serde_with_utils::float_key_to_string(k, precision)
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but `meta` is written first, under the reserved key `"__meta"`.
+  ///
+  /// This allows storing a version or other description alongside the entries, which can be read back with
+  /// [json_to_map_with_meta()](fn.json_to_map_with_meta.html). An error is returned if any key is converted to `"__meta"`.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<(i32, i32), String>::new();
+  /// map.insert((3, 5), "foo".to_string());
+  /// let ser = map.to_json_map_with_meta(&("v2", "(i32, i32)")).unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"__meta":["v2","(i32, i32)"],"[3,5]":"foo"}"#);
+  /// ```
+  fn to_json_map_with_meta<M: Serialize>(self, meta: M) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&SerializeWithMetaWrapper {
+      iter: RefCell::new(self.into_iter()),
+      meta
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but returns an error if any key is converted to an empty string.
+  ///
+  /// An empty key is valid JSON, but several keys producing it would collapse into a single entry when read back,
+  /// which usually indicates a bug in a custom `Serialize` impl.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<String, i32>::new();
+  /// map.insert("".to_string(), 5);
+  ///
+  /// assert_eq!(map.to_json_map().unwrap(), r#"{"":5}"#);
+  /// assert_eq!(map.to_json_map_strict().unwrap_err().to_string(), "key is an empty string");
+  /// ```
+  fn to_json_map_strict(self) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&SerializeMapIterWithKeyWrapper {
+      iter: RefCell::new(self.into_iter()),
+      key: |k: &K| match crate::serde_with_utils::key_to_string(k)? {
+        key_string if key_string.is_empty() => Err(serde_json::Error::custom("key is an empty string")),
+        key_string => Ok(key_string.into_owned())
+      }
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the output is written to `writer` prefixed with its length
+  /// in bytes, as a little-endian `u32`.
+  ///
+  /// This allows several maps to be sent over one stream. They can be read back with [json_to_map_framed()](fn.json_to_map_framed.html).
+  /// An error is returned if the output is longer than `u32::MAX` bytes.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<i32, i32>::new();
+  /// map.insert(5, 6);
+  /// let mut frame = Vec::new();
+  /// map.to_json_map_framed(&mut frame).unwrap();
+  ///
+  /// assert_eq!(frame, b"\x07\x00\x00\x00{\"5\":6}");
+  /// ```
+  fn to_json_map_framed<W: std::io::Write>(self, mut writer: W) -> Result<(), serde_json::Error> {
+    let buf = serde_json::to_vec(&SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter())
+    })?;
+    let len = u32::try_from(buf.len()).map_err(|_| serde_json::Error::custom("JSON map is too long to be framed"))?;
+    writer.write_all(&len.to_le_bytes()).map_err(serde_json::Error::io)?;
+    writer.write_all(&buf).map_err(serde_json::Error::io)
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the output is appended to any `std::fmt::Write`, such as a `String`.
+  ///
+  /// The entries are written directly to `writer`, so no intermediate string is allocated.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use std::fmt::Write;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<(i32, i32), &str>::new();
+  /// map.insert((3, 5), "é");
+  ///
+  /// let mut report = String::new();
+  /// write!(report, "counts: ").unwrap();
+  /// map.write_json_map(&mut report).unwrap();
+  ///
+  /// assert_eq!(report, r#"counts: {"[3,5]":"é"}"#);
+  /// ```
+  fn write_json_map<W: std::fmt::Write>(self, writer: &mut W) -> Result<(), serde_json::Error> {
+    let mut adapter = crate::serde_with_utils::FmtWriter::new(writer);
+    serde_json::to_writer(&mut adapter, &SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter())
+    })?;
+    std::io::Write::flush(&mut adapter).map_err(serde_json::Error::io)
+  }
+
+  /// Returns a wrapper which implements `Display` by calling [write_json_map()](#method.write_json_map), for use in logging and `format!()`.
+  ///
+  /// Nothing is serialized until the wrapper is formatted, and the output is written straight into the formatter without building a `String`.
+  /// If serialization fails, `<serialization error>` is written after the output produced so far,
+  /// so the error does not need to be handled inline.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<(i32, i32), i32>::new();
+  /// map.insert((3, 5), 6);
+  ///
+  /// assert_eq!(format!("state: {}", map.as_json_map_display()), r#"state: {"[3,5]":6}"#);
+  ///
+  /// let bad = vec![(f64::NAN, 1)];
+  /// assert_eq!(format!("{}", bad.iter().map(|(k, v)| (k, v)).as_json_map_display()), "{<serialization error>");
+  /// ```
+  // self is a reference or an iterator over one, so it is cheap to take by value
+  #[allow(clippy::wrong_self_convention)]
+  fn as_json_map_display(self) -> impl std::fmt::Display + 'a where
+  Self: Clone + 'a
+  {
+    JsonMapDisplay(self)
+  }
+
+  /// Returns the length in bytes of the string which [to_json_map()](#method.to_json_map) would produce.
+  ///
+  /// The entries are serialized into a sink that only counts bytes, so the output string is never allocated.
+  /// This can be used to preallocate a buffer, or to reject oversized outputs before serializing them.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde_json_any_key::*;
+  ///
+  /// let mut map = HashMap::<i32, String>::new();
+  /// map.insert(5, "foo".to_string());
+  /// let len = map.json_map_len().unwrap();
+  ///
+  /// assert_eq!(len, map.to_json_map().unwrap().len());
+  /// ```
+  fn json_map_len(self) -> Result<usize, serde_json::Error> {
+    let mut counter = crate::serde_with_utils::ByteCounter::default();
+    serde_json::to_writer(&mut counter, &SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter())
+    })?;
+    Ok(counter.count)
+  }
 }
 
 impl<'a,K,V,T> MapIterToJson<'a,K,V> for T where
 T: IntoIterator<Item=(&'a K,&'a V)>,
 K: 'a + Serialize + Any,
-V: 'a + Serialize,
+V: 'a + Serialize + ?Sized,
 <Self as IntoIterator>::IntoIter: 'a
 { }
 
+/// Serialize an `IntoIterator<Item=(&K,&V)>` as a JSON map into the given `Serializer`, converting the keys as
+/// [to_json_map()](trait.MapIterToJson.html#method.to_json_map) does.
+///
+/// This is the same serialization that [any_key_map](any_key_map/index.html) performs for a `#[serde(with)]` field.
+/// It allows the map to be written with a configured `serde_json::Serializer`, such as one with a custom formatter,
+/// or in the middle of a larger document which is being serialized by hand.
+///
+/// See [serialize_vec_into()](fn.serialize_vec_into.html) for `Vec<(K,V)>`
+/// and [serialize_consuming_into()](fn.serialize_consuming_into.html) for iterators which yield owned entries.
+///
+/// # Examples
+/// ```
+/// use std::collections::BTreeMap;
+/// use serde_json_any_key::*;
+///
+/// let mut map = BTreeMap::<(i32, i32), i32>::new();
+/// map.insert((3, 5), 6);
+///
+/// let mut buf = Vec::new();
+/// let mut serializer = serde_json::Serializer::pretty(&mut buf);
+/// serialize_map_into(&map, &mut serializer).unwrap();
+///
+/// assert_eq!(String::from_utf8(buf).unwrap(), "{\n  \"[3,5]\": 6\n}");
+/// ```
+pub fn serialize_map_into<'a,S,I,K,V>(iter: I, serializer: S) -> Result<S::Ok, S::Error> where
+  S: Serializer,
+  I: IntoIterator<Item=(&'a K,V)>,
+  K: 'a + Serialize + Any,
+  V: Serialize
+{
+  SerializeMapIterWrapper {
+    iter: RefCell::new(iter.into_iter())
+  }.serialize(serializer)
+}
+
+// Return type of as_json_map_display(), which serializes a copy of the iterator each time it is formatted
+struct JsonMapDisplay<T>(T);
+
+impl<'a,K,V,T> std::fmt::Display for JsonMapDisplay<T> where
+T: IntoIterator<Item=(&'a K,&'a V)> + Clone,
+K: 'a + Serialize + Any,
+V: 'a + Serialize + ?Sized,
+<T as IntoIterator>::IntoIter: 'a
+{
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.0.clone().write_json_map(f) {
+      Ok(()) => Ok(()),
+      // the formatter itself failed, so there is nowhere to write the marker
+      Err(e) if e.is_io() => Err(std::fmt::Error),
+      Err(_) => f.write_str("<serialization error>")
+    }
+  }
+}
+
+// V is the type of the value as yielded by the iterator, usually a reference
 pub(crate) struct SerializeMapIterWrapper<'a,K,V,I> where
-I: Iterator<Item=(&'a K,&'a V)>,
-K: 'a,
-V: 'a
+I: Iterator<Item=(&'a K,V)>,
+K: 'a
 {
   pub iter: RefCell<I>
 }
 
 impl<'a,K,V,I> Serialize for SerializeMapIterWrapper<'a,K,V,I> where
-  I: Iterator<Item=(&'a K,&'a V)>,
+  I: Iterator<Item=(&'a K,V)>,
   K: Serialize + Any,
   V: Serialize,
 {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
     S: Serializer
   {
-    let mut ser_map = serializer.serialize_map(None)?;
-    let mut iter = self.iter.borrow_mut();
-    // handle strings specially so they don't get escaped and wrapped inside another string
-    // compiler seems to be able to optimize this branch away statically
-    if TypeId::of::<K>() == TypeId::of::<String>() {
-      while let Some((k, v)) = iter.next() {
-        let s = (k as &dyn Any).downcast_ref::<String>().ok_or(S::Error::custom("Failed to serialize String as string"))?;
-        ser_map.serialize_entry(s, &v)?;
-      }
-    } else {
-      while let Some((k, v)) = iter.next() {
-        ser_map.serialize_entry(match &serde_json::to_string(&k)
-        {
-          Ok(key_string) => key_string,
-          Err(e) => { return Err(e).map_err(S::Error::custom); }
-        }, &v)?;
+    // a Serialize impl which re-enters this wrapper would otherwise panic on the second borrow
+    let mut iter = self.iter.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    let mut ser_map = serializer.serialize_map(crate::serde_with_utils::len_hint(&*iter))?;
+    crate::serde_with_utils::serialize_map_entries(&mut ser_map, iter.by_ref())?;
+    ser_map.end()
+  }
+}
+
+// Same as SerializeMapIterWrapper, but the keys are passed to the serializer unconverted, which is only correct for String keys
+struct SerializeStringKeyMapWrapper<I> {
+  iter: RefCell<I>
+}
+
+impl<'a,K,V,I> Serialize for SerializeStringKeyMapWrapper<I> where
+  I: Iterator<Item=(&'a K,V)>,
+  K: 'a + Serialize,
+  V: Serialize
+{
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+    S: Serializer
+  {
+    let mut iter = self.iter.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    serializer.collect_map(iter.by_ref())
+  }
+}
+
+// Same as SerializeMapIterWrapper, but each key is converted to a string by `key` instead of key_to_string()
+struct SerializeMapIterWithKeyWrapper<I,F> {
+  iter: RefCell<I>,
+  key: F
+}
+
+impl<'a,K,V,I,F> Serialize for SerializeMapIterWithKeyWrapper<I,F> where
+  I: Iterator<Item=(&'a K,V)>,
+  K: 'a,
+  V: Serialize,
+  F: Fn(&K) -> Result<String, serde_json::Error>
+{
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+    S: Serializer
+  {
+    let mut iter = self.iter.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    let mut ser_map = serializer.serialize_map(crate::serde_with_utils::len_hint(&*iter))?;
+    for (k, v) in iter.by_ref() {
+      let key_string = (self.key)(k).map_err(S::Error::custom)?;
+      ser_map.serialize_entry(&key_string, &v)?;
+    }
+    ser_map.end()
+  }
+}
+
+// Same as SerializeMapIterWrapper, but writes `meta` under the reserved key first
+struct SerializeWithMetaWrapper<I,M> {
+  iter: RefCell<I>,
+  meta: M
+}
+
+impl<'a,K,V,I,M> Serialize for SerializeWithMetaWrapper<I,M> where
+  I: Iterator<Item=(&'a K,V)>,
+  K: 'a + Serialize + Any,
+  V: Serialize,
+  M: Serialize
+{
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+    S: Serializer
+  {
+    let mut iter = self.iter.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    let mut ser_map = serializer.serialize_map(crate::serde_with_utils::len_hint(&*iter).map(|len| len + 1))?;
+    ser_map.serialize_entry(crate::serde_with_utils::META_KEY, &self.meta)?;
+    for (k, v) in iter.by_ref() {
+      let key_string = crate::serde_with_utils::key_to_string(k).map_err(S::Error::custom)?;
+      if key_string == crate::serde_with_utils::META_KEY {
+        return Err(S::Error::custom("key conflicts with the reserved \"__meta\" key"));
       }
+      ser_map.serialize_entry(&key_string, &v)?;
     }
     ser_map.end()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::SerializeMapIterWrapper;
+  use std::cell::RefCell;
+  use std::collections::HashMap;
+
+  #[test]
+  fn test_reentrant_serialize_is_error() {
+    let mut data = HashMap::<i32, i32>::new();
+    data.insert(5, 6);
+    let wrap = SerializeMapIterWrapper {
+      iter: RefCell::new(data.iter())
+    };
+    {
+      // simulates a Serialize impl which re-enters the wrapper while it is serializing
+      let _guard = wrap.iter.borrow_mut();
+      let err = serde_json::to_string(&wrap).unwrap_err();
+      assert_eq!(err.to_string(), "iterator is already being serialized");
+    }
+    assert_eq!(serde_json::to_string(&wrap).unwrap(), "{\"5\":6}");
+  }
+}
@@ -0,0 +1,63 @@
+
+use std::any::Any;
+use std::cell::RefCell;
+use serde::ser::Serialize;
+use crate::map_iter_to_json::SerializeMapIterWrapper;
+
+/// Blanket impl [ref_keys_to_json_map()](trait.RefKeyMapIterToJson.html#method.ref_keys_to_json_map) for all `IntoIterator<Item=(&&K,&V)>` types.
+pub trait RefKeyMapIterToJson<'a,'b,K,V>: IntoIterator<Item=(&'a &'b K,&'a V)> where
+Self: Sized,
+K: 'b + Serialize + Any,
+V: 'a + Serialize + ?Sized,
+'b: 'a,
+<Self as IntoIterator>::IntoIter: 'a
+{
+  /// Serialize any `IntoIterator<(&&K,&V)>` to a JSON map. This is intended for maps whose keys are borrowed from elsewhere, such as:  
+  /// `HashMap<&K,V>`  
+  /// return type of `HashMap<&K,V>::iter()`  
+  /// `BTreeMap<&K,V>`  
+  ///
+  /// The keys are dereferenced before serialization, so the output is identical to that of
+  /// [to_json_map()](trait.MapIterToJson.html#method.to_json_map) on a map with owned keys.
+  ///
+  /// **This does not consume self**. It is a separate method from `to_json_map()` because
+  /// a borrowed key `&K` does not satisfy the `Any` bound unless it is `'static`.
+  ///
+  /// # Examples
+  /// ```
+  /// use std::collections::HashMap;
+  /// use serde::Serialize;
+  /// use serde_json::Error;
+  /// use serde_json_any_key::*;
+  ///
+  /// #[derive(Clone, Copy, Serialize, PartialEq, Eq, Hash)]
+  /// pub struct Test {
+  ///   pub a: i32,
+  ///   pub b: i32
+  /// }
+  ///
+  /// fn try_main() -> Result<(), Error> {
+  /// let keys = vec![Test {a: 3, b: 5}];
+  /// let mut map = HashMap::<&Test, Test>::new();
+  /// map.insert(&keys[0], Test {a: 7, b: 9});
+  ///
+  /// let ser = map.ref_keys_to_json_map()?; // map.iter().ref_keys_to_json_map() is also valid
+  ///
+  /// assert_eq!(ser, "{\"{\\\"a\\\":3,\\\"b\\\":5}\":{\"a\":7,\"b\":9}}");
+  /// Ok(()) }
+  /// try_main().unwrap();
+  /// ```
+  fn ref_keys_to_json_map(self) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter().map(|(k, v)| (*k, v)))
+    })
+  }
+}
+
+impl<'a,'b,K,V,T> RefKeyMapIterToJson<'a,'b,K,V> for T where
+T: IntoIterator<Item=(&'a &'b K,&'a V)>,
+K: 'b + Serialize + Any,
+V: 'a + Serialize + ?Sized,
+'b: 'a,
+<Self as IntoIterator>::IntoIter: 'a
+{ }
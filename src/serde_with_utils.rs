@@ -1,18 +1,59 @@
 
 use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::ffi::OsString;
 use std::marker::PhantomData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::PathBuf;
 use serde::de::{Deserialize, Deserializer, Visitor};
-use serde::de::{MapAccess};
+use serde::de::{MapAccess, SeqAccess};
+use serde::ser::{Serialize, SerializeMap};
 use std::fmt;
 
 // Some utilities required to implement any_key_map / any_key_vec
 
+/// Adapts any `serde::de::MapAccess` into an `Iterator<Item=Result<(K,V), A::Error>>`.
+///
+/// This is useful when hand-writing a `Visitor::visit_map()` implementation,
+/// as the entries can then be consumed with the usual iterator adaptors such as `collect()`.
+///
+/// # Examples
+/// ```
+/// use std::fmt;
+/// use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+/// use serde_json_any_key::MapIter;
+///
+/// // Collects the entries of a JSON map into a Vec, preserving their order
+/// #[derive(Debug, PartialEq)]
+/// struct Entries(Vec<(String, i32)>);
+///
+/// impl<'de> Deserialize<'de> for Entries {
+///   fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+///     struct EntriesVisitor;
+///     impl<'de> Visitor<'de> for EntriesVisitor {
+///       type Value = Entries;
+///       fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(formatter, "a JSON map")
+///       }
+///       fn visit_map<A: MapAccess<'de>>(self, access: A) -> Result<Self::Value, A::Error> {
+///         MapIter::<'de, A, String, i32>::new(access).collect::<Result<_,_>>().map(Entries)
+///       }
+///     }
+///     deserializer.deserialize_map(EntriesVisitor)
+///   }
+/// }
+///
+/// let deser: Entries = serde_json::from_str(r#"{"foo":1,"bar":2}"#).unwrap();
+/// assert_eq!(deser, Entries(vec![("foo".to_string(), 1), ("bar".to_string(), 2)]));
+/// ```
 pub struct MapIter<'de,A,K,V> {
+  /// The underlying `MapAccess`. Entries are read from it lazily as the iterator advances.
   pub access: A,
   marker: PhantomData<(&'de (),K,V)>,
 }
 
 impl<'de,A,K,V> MapIter<'de,A,K,V> {
+    /// Wraps `access` so that its entries can be iterated as `(K,V)` tuples.
     pub fn new(access: A) -> Self
     where
         A: serde::de::MapAccess<'de>,
@@ -44,53 +85,648 @@ where
     }
 }
 
-// any_key_map and any_key_vec use the same deserialize function
+// Deserializes a map, for any_key_vec, any_key_map::nested and json_to_vec().
+// any_key_map itself uses deserialize_map_or_pairs(), which also accepts an array of pairs.
 #[inline]
 pub fn deserialize<'d,D,C,K,V>(deserializer: D) -> Result<C,D::Error> where
   D: Deserializer<'d>,
   C: FromIterator<(K,V)> + Sized,
   for<'de> K: Deserialize<'de> + Any + 'd,
-  for<'de> V: Deserialize<'de> + 'd,
+  for<'de> V: Deserialize<'de>,
 {
-  struct Helper<C,K,V>(PhantomData<(C,K,V)>);
-  impl<'d,C,K,V> Visitor<'d> for Helper<C,K,V>
-  where
+  deserializer.deserialize_map(Helper(false, PhantomData))
+}
+
+// Same as deserialize(), but also accepts an array of [key, value] pairs with native keys.
+// Only self-describing formats can tell the two apart, so human-readable formats such as JSON are asked for
+// whichever they contain, while other formats (which may not support deserialize_any) are still asked for a map.
+#[inline]
+pub(crate) fn deserialize_map_or_pairs<'d,D,C,K,V>(deserializer: D) -> Result<C,D::Error> where
+  D: Deserializer<'d>,
   C: FromIterator<(K,V)> + Sized,
   for<'de> K: Deserialize<'de> + Any + 'd,
-  for<'de> V: Deserialize<'de> + 'd
+  for<'de> V: Deserialize<'de>,
+{
+  if deserializer.is_human_readable() {
+    deserializer.deserialize_any(Helper(true, PhantomData))
+  } else {
+    deserializer.deserialize_map(Helper(true, PhantomData))
+  }
+}
+
+// The flag is true if an array of pairs is also expected
+struct Helper<C,K,V>(bool, PhantomData<(C,K,V)>);
+
+impl<'d,C,K,V> Visitor<'d> for Helper<C,K,V>
+where
+C: FromIterator<(K,V)> + Sized,
+for<'de> K: Deserialize<'de> + Any + 'd,
+for<'de> V: Deserialize<'de>
+{
+    type Value = C;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 {
+            write!(formatter, "a JSON map with stringified keys, or an array of [key, value] pairs")
+        } else {
+            write!(formatter, "a JSON map with stringified keys")
+        }
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'d>,
+    {
+      // https://stackoverflow.com/a/26370894/19260728
+      // the zero-based entry index is appended to errors, since byte offsets are hard to map back to a key
+      let mut index = 0;
+      std::iter::from_fn(|| {
+        let res = next_entry(&mut access)
+          .map_err(|e| serde::de::Error::custom(format_args!("{} at entry {}", e, index)))
+          .transpose();
+        index += 1;
+        res
+      }).collect()
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'d>,
+    {
+      if !self.0 {
+        return Err(serde::de::Error::invalid_type(serde::de::Unexpected::Seq, &self));
+      }
+      let mut index = 0;
+      std::iter::from_fn(|| {
+        let res = seq.next_element::<(Buffered<K>,Buffered<V>)>()
+          .map(|entry| entry.map(|(k, v)| (k.0, v.0)))
+          .map_err(|e| serde::de::Error::custom(format_args!("{} at entry {}", e, index)))
+          .transpose();
+        index += 1;
+        res
+      }).collect()
+    }
+}
+
+// Reads the next entry of a streamed map. The key is read first, so that value errors can be reported by invalid_value().
+fn next_entry<'d,A,K,V>(access: &mut A) -> Result<Option<(K,V)>, A::Error> where
+  A: MapAccess<'d>,
+  for<'de> K: Deserialize<'de> + Any,
+  for<'de> V: Deserialize<'de>
+{
+  let key: String = match access.next_key()? {
+    Some(key) => key,
+    None => { return Ok(None); }
+  };
+  let key_obj: K = string_to_key(key.as_str()).map_err(serde::de::Error::custom)?;
+  let val_obj: Buffered<V> = access.next_value().map_err(|e| serde::de::Error::custom(invalid_value(&key, e)))?;
+  Ok(Some((key_obj, val_obj.0)))
+}
+
+// A map key which borrows from the input when it contains no escape sequences, so that it does not need to be allocated
+struct BorrowedKey<'d>(Cow<'d, str>);
+
+impl<'d> Deserialize<'d> for BorrowedKey<'d> {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+    D: Deserializer<'d>
   {
-      type Value = C;
+    struct KeyVisitor;
+    impl<'d> Visitor<'d> for KeyVisitor {
+      type Value = BorrowedKey<'d>;
 
       fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-          write!(formatter, "a JSON map")
+        write!(formatter, "a string key")
+      }
+
+      fn visit_borrowed_str<E>(self, v: &'d str) -> Result<Self::Value, E> {
+        Ok(BorrowedKey(Cow::Borrowed(v)))
+      }
+
+      fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(BorrowedKey(Cow::Owned(v.to_string())))
+      }
+
+      fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(BorrowedKey(Cow::Owned(v)))
+      }
+    }
+    deserializer.deserialize_str(KeyVisitor)
+  }
+}
+
+// Streams the entries of a map into a BTreeMap, parsing each key from the borrowed input where possible.
+pub(crate) fn deserialize_btreemap<'d,D,K,V>(deserializer: D) -> Result<std::collections::BTreeMap<K,V>, D::Error> where
+  D: Deserializer<'d>,
+  for<'de> K: Deserialize<'de> + Ord + Any,
+  for<'de> V: Deserialize<'de>
+{
+  struct BTreeVisitor<K,V>(PhantomData<(K,V)>);
+  impl<'d,K,V> Visitor<'d> for BTreeVisitor<K,V> where
+    for<'de> K: Deserialize<'de> + Ord + Any,
+    for<'de> V: Deserialize<'de>
+  {
+    type Value = std::collections::BTreeMap<K,V>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(formatter, "a JSON map with stringified keys")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error> where
+      A: MapAccess<'d>
+    {
+      let mut map = std::collections::BTreeMap::new();
+      let mut index = 0;
+      let at_entry = |index: usize| move |e: A::Error| serde::de::Error::custom(format_args!("{} at entry {}", e, index));
+      while let Some(key) = access.next_key::<BorrowedKey<'d>>().map_err(at_entry(index))? {
+        let key_obj: K = string_to_key(key.0.as_ref()).map_err(|e| serde::de::Error::custom(format_args!("{} at entry {}", e, index)))?;
+        let val_obj: Buffered<V> = access.next_value()
+          .map_err(|e| serde::de::Error::custom(invalid_value(&key.0, e)))
+          .map_err(at_entry(index))?;
+        map.insert(key_obj, val_obj.0);
+        index += 1;
       }
+      Ok(map)
+    }
+  }
+  deserializer.deserialize_map(BTreeVisitor(PhantomData))
+}
+
+// Deserializes a V from the streaming deserializer.
+// With arbitrary_precision, serde_json passes numbers to buffering types (such as internally tagged enums) as a private map,
+// which they then reject, so in that case the entry is parsed into a serde_json::Value first, which understands that map.
+// Non-human-readable formats may not support deserialize_any, and are passed through unchanged.
+pub(crate) struct Buffered<V>(pub V);
+
+impl<'d,V> Deserialize<'d> for Buffered<V> where
+  for<'de> V: Deserialize<'de>
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+    D: Deserializer<'d>
+  {
+    if cfg!(feature = "arbitrary_precision") && deserializer.is_human_readable() {
+      let value = serde_json::Value::deserialize(deserializer)?;
+      return V::deserialize(value).map(Buffered).map_err(serde::de::Error::custom);
+    }
+    V::deserialize(deserializer).map(Buffered)
+  }
+}
 
-      fn visit_map<A>(self, seq: A) -> Result<Self::Value, A::Error>
-      where
-          A: MapAccess<'d>,
-      {
-        // https://stackoverflow.com/a/26370894/19260728
-        let coll: Result<C, A::Error> = MapIter::<'d, A, String, V>::new(seq)
-          .map(|res| {
-            res.and_then(|value: (String,V)| {
-              // handle strings specially as they are not objects
-              // compiler seems to be able to optimize this branch away statically
-              let key_obj: K = match TypeId::of::<K>() == TypeId::of::<String>() {
-                true => match <K as Deserialize>::deserialize(serde_json::Value::from(value.0)) {
-                  Ok(k) => k,
-                  Err(e) => { return Err(e).map_err(serde::de::Error::custom); }
-                },
-                false => match serde_json::from_str(&value.0) {
-                  Ok(k) => k,
-                  Err(e) => { return Err(e).map_err(serde::de::Error::custom); }
-                }
-              };
-              Ok((key_obj, value.1))
-            })
-          }).collect();
-        coll
+/// Serializes each `(&K,V)` entry into an already started `SerializeMap`, converting the keys exactly as
+/// [to_json_map()](trait.MapIterToJson.html#method.to_json_map) would.
+///
+/// This is the entry loop shared by all of this crate's serializers. It is useful when writing a custom `Serialize` impl
+/// which combines entries from several collections into one JSON map. The caller is responsible for calling `end()`.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde::ser::{Serialize, Serializer, SerializeMap};
+/// use serde_json_any_key::*;
+///
+/// struct Merged<'a> {
+///   defaults: &'a HashMap<(i32, i32), String>,
+///   overrides: &'a Vec<((i32, i32), String)>
+/// }
+///
+/// impl Serialize for Merged<'_> {
+///   fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+///     let mut ser_map = serializer.serialize_map(None)?;
+///     serialize_map_entries(&mut ser_map, self.defaults.iter())?;
+///     serialize_map_entries(&mut ser_map, self.overrides.iter().map(|(k, v)| (k, v)))?;
+///     ser_map.end()
+///   }
+/// }
+///
+/// let mut defaults = HashMap::new();
+/// defaults.insert((3, 5), "foo".to_string());
+/// let overrides = vec![((7, 9), "bar".to_string())];
+/// let ser = serde_json::to_string(&Merged { defaults: &defaults, overrides: &overrides }).unwrap();
+///
+/// assert_eq!(ser, r#"{"[3,5]":"foo","[7,9]":"bar"}"#);
+/// ```
+pub fn serialize_map_entries<'a,M,I,K,V>(ser_map: &mut M, iter: I) -> Result<(), M::Error> where
+  M: SerializeMap,
+  I: IntoIterator<Item=(&'a K,V)>,
+  K: 'a + Serialize + Any,
+  V: Serialize
+{
+  for (k, v) in iter {
+    let key_string = key_to_string(k).map_err(serde::ser::Error::custom)?;
+    ser_map.serialize_entry(&key_string, &v)?;
+  }
+  Ok(())
+}
+
+// The number of remaining entries, if the iterator reports it exactly, so that serializers which need the length up front can use it.
+pub(crate) fn len_hint<I: Iterator>(iter: &I) -> Option<usize> {
+  match iter.size_hint() {
+    (lower, Some(upper)) if lower == upper => Some(lower),
+    _ => None
+  }
+}
+
+// Convert a key to the string which will be used as the JSON map key.
+// String-like keys are used as-is so they don't get escaped and wrapped inside another string.
+// serde_json writes non-finite floats as null, which could never be parsed back into the float key,
+// so reject them up front instead of producing a map that can't be deserialized.
+// Other keys are written by serde_json::to_string(), which shares serde_json's feature configuration
+// (such as arbitrary_precision) with the value path, so numeric keys and values are formatted identically.
+// compiler seems to be able to optimize these branches away statically
+pub(crate) fn key_to_string<K>(key: &K) -> Result<Cow<'_, str>, serde_json::Error> where
+  K: serde::Serialize + Any
+{
+  let key_any = key as &dyn Any;
+  if TypeId::of::<K>() == TypeId::of::<String>() {
+    let s = key_any.downcast_ref::<String>().ok_or_else(|| serde::ser::Error::custom("Failed to serialize String as string"))?;
+    return Ok(Cow::Borrowed(s));
+  }
+  if TypeId::of::<K>() == TypeId::of::<PathBuf>() {
+    let p = key_any.downcast_ref::<PathBuf>().ok_or_else(|| serde::ser::Error::custom("Failed to serialize PathBuf as string"))?;
+    return p.to_str().map(Cow::Borrowed).ok_or_else(|| serde::ser::Error::custom("path contains invalid UTF-8 characters"));
+  }
+  if TypeId::of::<K>() == TypeId::of::<OsString>() {
+    let o = key_any.downcast_ref::<OsString>().ok_or_else(|| serde::ser::Error::custom("Failed to serialize OsString as string"))?;
+    return o.to_str().map(Cow::Borrowed).ok_or_else(|| serde::ser::Error::custom("OsString contains invalid UTF-8 characters"));
+  }
+  if is_net_addr::<K>() {
+    // addresses serialize as JSON strings, which would otherwise be quoted a second time
+    return match serde_json::to_value(key)? {
+      serde_json::Value::String(s) => Ok(Cow::Owned(s)),
+      _ => Err(serde::ser::Error::custom("Failed to serialize address as string"))
+    };
+  }
+  json_key_to_string(key).map(Cow::Owned)
+}
+
+// Convert a key with serde_json::to_string() and no special cases for string-like keys.
+// Non-finite floats are still rejected, as serde_json would write them as null.
+pub(crate) fn json_key_to_string<K>(key: &K) -> Result<String, serde_json::Error> where
+  K: serde::Serialize + Any
+{
+  let key_any = key as &dyn Any;
+  let finite = match (key_any.downcast_ref::<f64>(), key_any.downcast_ref::<f32>()) {
+    (Some(f), _) => f.is_finite(),
+    (_, Some(f)) => f.is_finite(),
+    _ => true
+  };
+  if !finite {
+    return Err(serde::ser::Error::custom("float key must be finite"));
+  }
+  serde_json::to_string(key)
+}
+
+// Reverses key_to_string(), converting a JSON map key back into the native key type.
+// handle string-like keys specially as they are not objects
+// compiler seems to be able to optimize these branches away statically
+pub(crate) fn string_to_key<K,S>(key: S) -> Result<K, serde_json::Error> where
+  for<'de> K: Deserialize<'de> + Any,
+  S: AsRef<str> + Into<String>
+{
+  if TypeId::of::<K>() == TypeId::of::<PathBuf>() && key.as_ref().starts_with('"') {
+    // keys written by 2.0.0 and earlier are still quoted; a path which is itself a valid JSON string is read the same way
+    if let Ok(k) = serde_json::from_str(key.as_ref()) {
+      return Ok(k);
+    }
+  }
+  if TypeId::of::<K>() == TypeId::of::<String>() {
+    return cast(key.into()).map_err(|key: String| invalid_key(&key, "Failed to deserialize String from string"));
+  }
+  if TypeId::of::<K>() == TypeId::of::<PathBuf>() {
+    return cast(PathBuf::from(key.into())).map_err(|p| invalid_key(&p.to_string_lossy(), "Failed to deserialize PathBuf from string"));
+  }
+  if is_net_addr::<K>() {
+    let key = key.into();
+    // keys written before addresses were special-cased are still quoted
+    if key.starts_with('"') {
+      return serde_json::from_str(&key).map_err(|e| invalid_key(&key, e));
+    }
+    return <K as Deserialize>::deserialize(serde_json::Value::from(key.as_str())).map_err(|e| invalid_key(&key, e));
+  }
+  if TypeId::of::<K>() == TypeId::of::<OsString>() {
+    // keys written by 2.0.0 and earlier use serde's platform-specific enum, such as {"Unix":[102,111,111]}
+    if key.as_ref().starts_with('{') {
+      if let Ok(k) = serde_json::from_str(key.as_ref()) {
+        return Ok(k);
       }
+    }
+    // serde represents OsString as a platform-specific enum, so build it directly instead
+    return cast(OsString::from(key.into())).map_err(|o| invalid_key(&o.to_string_lossy(), "Failed to deserialize OsString from string"));
+  }
+  serde_json::from_str(key.as_ref()).map_err(|e| invalid_key(key.as_ref(), e))
+}
+
+// Moves a value into K when both are the same type, so that owned key strings are not copied.
+// Returns the value unchanged if the types differ.
+fn cast<T: Any, K: Any>(value: T) -> Result<K, T> {
+  let mut slot = Some(value);
+  match (&mut slot as &mut dyn Any).downcast_mut::<Option<K>>().and_then(Option::take) {
+    Some(k) => Ok(k),
+    None => Err(slot.expect("the value is only taken when the types match"))
+  }
+}
+
+// Converts the key of an entry whose value has already been deserialized, moving the key string instead of copying it.
+// Key errors take precedence over value errors, as they would if the key were converted first.
+pub(crate) fn entry_from_parts<K,V,E>(key: String, val: Result<V,E>) -> Result<(K,V), serde_json::Error> where
+  for<'de> K: Deserialize<'de> + Any,
+  E: fmt::Display
+{
+  match val {
+    Ok(v) => Ok((string_to_key(key)?, v)),
+    Err(e) => {
+      string_to_key::<K,_>(key.as_str())?;
+      Err(invalid_value(&key, e))
+    }
+  }
+}
+
+// The reserved key written by to_json_map_with_meta()
+pub(crate) const META_KEY: &str = "__meta";
+
+// Returns false for the key types which are written as plain strings rather than JSON text
+pub(crate) fn key_is_json<K: Any>() -> bool {
+  let id = TypeId::of::<K>();
+  !(id == TypeId::of::<String>() || id == TypeId::of::<PathBuf>() || id == TypeId::of::<OsString>() || is_net_addr::<K>())
+}
+
+// std::net address types, which are written as plain strings like String keys
+fn is_net_addr<K: Any>() -> bool {
+  let id = TypeId::of::<K>();
+  id == TypeId::of::<IpAddr>() || id == TypeId::of::<Ipv4Addr>() || id == TypeId::of::<Ipv6Addr>() ||
+  id == TypeId::of::<SocketAddr>() || id == TypeId::of::<SocketAddrV4>() || id == TypeId::of::<SocketAddrV6>()
+}
+
+// Prefix entry errors so that callers can tell whether the key or the value was invalid
+pub(crate) fn invalid_key<E: fmt::Display>(key: &str, e: E) -> serde_json::Error {
+  serde::de::Error::custom(format_args!("invalid key {:?}: {}", key, without_position(&e.to_string())))
+}
+
+pub(crate) fn invalid_value<E: fmt::Display>(key: &str, e: E) -> serde_json::Error {
+  serde::de::Error::custom(format_args!("invalid value for key {:?}: {}", key, without_position(&e.to_string())))
+}
+
+// serde_json appends the position within the key or value being parsed, which would read as a position in the document,
+// and would be followed by a second position when the error is reported while reading the document
+fn without_position(msg: &str) -> &str {
+  if let Some(i) = msg.rfind(" at line ") {
+    if let Some((line, column)) = msg[i + " at line ".len()..].split_once(" column ") {
+      let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+      if is_number(line) && is_number(column) {
+        return &msg[..i];
+      }
+    }
+  }
+  msg
+}
+
+// Parses a document which must contain a JSON map.
+pub(crate) fn parse_json_map(str: &str) -> Result<serde_json::Map<String, serde_json::Value>, serde_json::Error> {
+  match serde_json::from_str(str)? {
+    serde_json::Value::Object(o) => Ok(o),
+    _ => Err(serde::de::Error::custom("Value is not a JSON map"))
+  }
+}
+
+// The entry loop shared by the deserializers which parse the whole document first.
+// Each key is converted by `key_fn`, which should report failures with invalid_key(),
+// and each value is deserialized into V, with failures reported by invalid_value().
+pub(crate) fn for_each_entry<'a,I,K,V,KF,F>(entries: I, mut key_fn: KF, mut f: F) -> Result<(), serde_json::Error> where
+  I: IntoIterator<Item=(&'a String, &'a serde_json::Value)>,
+  for<'de> V: Deserialize<'de>,
+  KF: FnMut(&'a str) -> Result<K, serde_json::Error>,
+  F: FnMut(K, V)
+{
+  for (key, val) in entries {
+    let key_obj: K = key_fn(key)?;
+    let val_obj: V = <V as Deserialize>::deserialize(val).map_err(|e| invalid_value(key, e))?;
+    f(key_obj, val_obj);
+  }
+  Ok(())
+}
+
+// An io::Write sink which discards its input, keeping only the number of bytes written.
+#[derive(Default)]
+pub(crate) struct ByteCounter {
+  pub count: usize
+}
+
+impl std::io::Write for ByteCounter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.count += buf.len();
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+// Returns true if the arrays and objects in the JSON text `s` are nested more than `limit` levels deep.
+// The text is only scanned, not validated, so that the check is cheap and cannot itself recurse.
+pub(crate) fn exceeds_depth(s: &str, limit: usize) -> bool {
+  let mut depth = 0usize;
+  let mut in_string = false;
+  let mut escaped = false;
+  for b in s.bytes() {
+    if in_string {
+      match b {
+        _ if escaped => { escaped = false; },
+        b'\\' => { escaped = true; },
+        b'"' => { in_string = false; },
+        _ => {}
+      }
+      continue;
+    }
+    match b {
+      b'"' => { in_string = true; },
+      b'[' | b'{' => {
+        depth += 1;
+        if depth > limit { return true; }
+      },
+      b']' | b'}' => { depth = depth.saturating_sub(1); },
+      _ => {}
+    }
+  }
+  false
+}
+
+// Adapts a fmt::Write into an io::Write, so that serde_json can write into it.
+// serde_json only writes valid UTF-8, but a write could still end inside a multi-byte character,
+// so any incomplete trailing bytes are held back until the next write.
+pub(crate) struct FmtWriter<'w,W: fmt::Write> {
+  pub inner: &'w mut W,
+  pending: Vec<u8>
+}
+
+impl<'w,W: fmt::Write> FmtWriter<'w,W> {
+  pub fn new(inner: &'w mut W) -> Self {
+    FmtWriter { inner, pending: vec![] }
+  }
+}
+
+impl<W: fmt::Write> std::io::Write for FmtWriter<'_,W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.pending.extend_from_slice(buf);
+    let valid = match std::str::from_utf8(&self.pending) {
+      Ok(s) => s.len(),
+      Err(e) if e.error_len().is_none() => e.valid_up_to(),
+      Err(e) => { return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)); }
+    };
+    // the prefix was just validated
+    let s = std::str::from_utf8(&self.pending[..valid]).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    self.inner.write_str(s).map_err(std::io::Error::other)?;
+    self.pending.drain(..valid);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    if self.pending.is_empty() {
+      Ok(())
+    } else {
+      Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "incomplete UTF-8 sequence"))
+    }
+  }
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Encode a key which serializes as a sequence of bytes (such as Vec<u8> or [u8; N]) as padded standard base64 (RFC 4648).
+pub(crate) fn key_to_base64<K: Serialize>(key: &K) -> Result<String, serde_json::Error> {
+  let bytes: Vec<u8> = match serde_json::to_value(key)? {
+    serde_json::Value::Array(a) => a.iter().map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok())).collect::<Option<_>>(),
+    _ => None
+  }.ok_or_else(|| serde::ser::Error::custom("key is not a sequence of bytes"))?;
+  let mut out = String::with_capacity(bytes.len() / 3 * 4 + 4);
+  for chunk in bytes.chunks(3) {
+    let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  Ok(out)
+}
+
+// Reverses key_to_base64(), decoding the bytes and deserializing the key from them as a sequence.
+// Only the canonical encoding is accepted, so the unused bits before the padding must be zero,
+// and each key has exactly one string form, as for the other key encodings.
+pub(crate) fn base64_to_key<K>(key: &str) -> Result<K, serde_json::Error> where
+  for<'de> K: Deserialize<'de>
+{
+  let digits = key.as_bytes();
+  if digits.len() % 4 != 0 {
+    return Err(invalid_key(key, "invalid base64 length"));
+  }
+  let mut bytes = Vec::with_capacity(digits.len() / 4 * 3);
+  for (c, chunk) in digits.chunks(4).enumerate() {
+    let last = c == digits.len() / 4 - 1;
+    let padding = chunk.iter().rev().take_while(|d| **d == b'=').count();
+    if padding > 2 || (padding > 0 && !last) {
+      return Err(invalid_key(key, "invalid base64 padding"));
+    }
+    let mut n = 0u32;
+    for (i, d) in chunk[..4 - padding].iter().enumerate() {
+      let v = BASE64_ALPHABET.iter().position(|a| a == d).ok_or_else(|| invalid_key(key, format_args!("invalid base64 character {:?}", *d as char)))?;
+      n |= (v as u32) << (18 - 6 * i);
+    }
+    if n & ((1 << (8 * padding)) - 1) != 0 {
+      return Err(invalid_key(key, "invalid base64 padding bits"));
+    }
+    bytes.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+  }
+  <K as Deserialize>::deserialize(serde_json::Value::from(bytes)).map_err(|e| invalid_key(key, e))
+}
+
+// Rebuilds every object within `value` with its keys inserted in sorted order,
+// so that the output is sorted even if serde_json's preserve_order feature is enabled.
+pub(crate) fn canonical_value(value: serde_json::Value) -> serde_json::Value {
+  match value {
+    serde_json::Value::Array(a) => serde_json::Value::Array(a.into_iter().map(canonical_value).collect()),
+    serde_json::Value::Object(o) => {
+      let mut entries: Vec<(String, serde_json::Value)> = o.into_iter().collect();
+      entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+      serde_json::Value::Object(entries.into_iter().map(|(k, v)| (k, canonical_value(v))).collect())
+    },
+    v => v
+  }
+}
+
+// Formats a key which serializes as a JSON number with a fixed number of decimals.
+pub(crate) fn float_key_to_string<K: Serialize>(key: &K, precision: usize) -> Result<String, serde_json::Error> {
+  match serde_json::to_value(key)? {
+    serde_json::Value::Number(n) => n.as_f64().map(|f| format!("{:.*}", precision, f)).ok_or_else(|| serde::ser::Error::custom("key is not a number")),
+    // non-finite floats serialize as null
+    serde_json::Value::Null => Err(serde::ser::Error::custom("float key must be finite")),
+    _ => Err(serde::ser::Error::custom("key is not a number"))
+  }
+}
+
+// Reverses float_key_to_string(), accepting any finite decimal number regardless of its precision.
+pub(crate) fn string_to_float_key<K>(key: &str) -> Result<K, serde_json::Error> where
+  for<'de> K: Deserialize<'de>
+{
+  let f: f64 = key.trim().parse().map_err(|e| invalid_key(key, e))?;
+  if !f.is_finite() {
+    return Err(invalid_key(key, "float key must be finite"));
+  }
+  <K as Deserialize>::deserialize(serde_json::Value::from(f)).map_err(|e| invalid_key(key, e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{FmtWriter, len_hint, key_to_base64, base64_to_key};
+  use std::collections::HashMap;
+  use std::io::Write;
+
+  #[test]
+  fn test_fmt_writer_split_characters() {
+    let mut out = String::new();
+    let mut writer = FmtWriter::new(&mut out);
+    let bytes = "aé🦀".as_bytes();
+    for b in bytes {
+      writer.write_all(std::slice::from_ref(b)).unwrap();
+    }
+    writer.flush().unwrap();
+    writer.write_all(&bytes[..2]).unwrap();
+    assert!(writer.flush().is_err());
+    assert_eq!(out, "aé🦀a");
+  }
+
+  #[test]
+  fn test_len_hint() {
+    let mut data = HashMap::<i32, i32>::new();
+    data.insert(5, 6);
+    data.insert(7, 8);
+    assert_eq!(len_hint(&data.iter()), Some(2));
+    assert_eq!(len_hint(&data.iter().map(|(k, v)| (k, v + 1))), Some(2));
+    assert_eq!(len_hint(&data.iter().filter(|(k, _v)| **k > 5)), None);
+    let mut iter = data.iter();
+    iter.next();
+    assert_eq!(len_hint(&iter), Some(1));
+  }
+
+  #[test]
+  fn test_base64_keys() {
+    // test vectors from RFC 4648
+    for (bytes, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")] {
+      let bytes = bytes.as_bytes().to_vec();
+      assert_eq!(key_to_base64(&bytes).unwrap(), encoded);
+      assert_eq!(base64_to_key::<Vec<u8>>(encoded).unwrap(), bytes);
+    }
+    let all: Vec<u8> = (0..=255).collect();
+    assert_eq!(base64_to_key::<Vec<u8>>(&key_to_base64(&all).unwrap()).unwrap(), all);
+
+    assert_eq!(key_to_base64(&"foo").unwrap_err().to_string(), "key is not a sequence of bytes");
+    assert_eq!(key_to_base64(&vec![256]).unwrap_err().to_string(), "key is not a sequence of bytes");
+    assert!(base64_to_key::<Vec<u8>>("Zm9").is_err());
+    assert!(base64_to_key::<Vec<u8>>("Zg==Zm8=").is_err());
+    assert!(base64_to_key::<Vec<u8>>("Zm9v!A==").is_err());
+    // the unused bits before the padding must be zero, so that each key has one encoding
+    assert!(base64_to_key::<Vec<u8>>("Zh==").is_err());
+    assert!(base64_to_key::<Vec<u8>>("Zm9=").is_err());
+    assert_eq!(base64_to_key::<Vec<u8>>("Zh==").unwrap_err().to_string(), "invalid key \"Zh==\": invalid base64 padding bits");
   }
-  
-  deserializer.deserialize_map(Helper(PhantomData))
 }
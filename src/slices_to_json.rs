@@ -0,0 +1,38 @@
+use std::any::Any;
+use std::cell::RefCell;
+use serde::ser::{Serialize, Error};
+use crate::map_iter_to_json::SerializeMapIterWrapper;
+
+/// Serialize two parallel slices of keys and values to a JSON map, pairing each key with the value at the same index.
+///
+/// This is useful for struct-of-arrays layouts, as no intermediate `Vec<(K,V)>` is created.
+/// The keys are converted exactly as [to_json_map()](trait.MapIterToJson.html#method.to_json_map) would.
+/// An error is returned if the slices have different lengths.
+///
+/// # Examples
+/// ```
+/// use serde_json::Error;
+/// use serde_json_any_key::*;
+///
+/// fn try_main() -> Result<(), Error> {
+/// let keys = vec![(3, 5), (7, 9)];
+/// let values = vec!["foo", "bar"];
+///
+/// let ser = slices_to_json_map(&keys, &values)?;
+/// assert_eq!(ser, r#"{"[3,5]":"foo","[7,9]":"bar"}"#);
+///
+/// assert!(slices_to_json_map(&keys, &values[..1]).is_err());
+/// Ok(()) }
+/// try_main().unwrap();
+/// ```
+pub fn slices_to_json_map<K,V>(keys: &[K], values: &[V]) -> Result<String, serde_json::Error> where
+K: Serialize + Any,
+V: Serialize
+{
+  if keys.len() != values.len() {
+    return Err(serde_json::Error::custom(format_args!("slice lengths differ: {} keys and {} values", keys.len(), values.len())));
+  }
+  serde_json::to_string(&SerializeMapIterWrapper {
+    iter: RefCell::new(keys.iter().zip(values.iter()))
+  })
+}
@@ -1,5 +1,5 @@
 
-use std::any::{Any, TypeId};
+use std::any::Any;
 use std::cell::RefCell;
 use serde::ser::{Serialize, Serializer, SerializeMap, Error};
 
@@ -54,6 +54,156 @@ V: 'a + Serialize,
       iter: RefCell::new(self.into_iter())
     })
   }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the output buffer is preallocated with `capacity` bytes.
+  ///
+  /// For large collections this avoids repeatedly growing the buffer during serialization.
+  /// The capacity can be computed exactly with [json_map_len()](#method.json_map_len).
+  ///
+  /// # Examples
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let vec = vec![(5, 6)];
+  /// let ser = vec.to_json_map_with_capacity(1024).unwrap();
+  ///
+  /// assert_eq!(ser, "{\"5\":6}");
+  /// assert!(ser.capacity() >= 1024);
+  /// ```
+  fn to_json_map_with_capacity(self, capacity: usize) -> Result<String, serde_json::Error> {
+    let mut buf = Vec::with_capacity(capacity);
+    serde_json::to_writer(&mut buf, &SerializeVecIterWrapper {
+      iter: RefCell::new(self.into_iter())
+    })?;
+    // serde_json only writes valid UTF-8
+    String::from_utf8(buf).map_err(serde_json::Error::custom)
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but only the entries for which `keep` returns `true` are serialized.
+  ///
+  /// Entries are filtered lazily as they are serialized, so no filtered copy of the collection is created.
+  ///
+  /// # Examples
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let vec = vec![(1, 0), (2, 5)];
+  /// let ser = vec.to_json_map_filtered(|_k, v| *v != 0).unwrap();
+  ///
+  /// assert_eq!(ser, "{\"2\":5}");
+  /// ```
+  fn to_json_map_filtered<F>(self, mut keep: F) -> Result<String, serde_json::Error> where
+  F: FnMut(&K, &V) -> bool
+  {
+    serde_json::to_string(&SerializeVecIterWrapper {
+      iter: RefCell::new(self.into_iter().filter(move |(k, v)| keep(k, v)))
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but each value is passed through `f` and the result is serialized in its place.
+  ///
+  /// The keys are unchanged. This can be used to redact or convert values without modifying or copying the collection.
+  ///
+  /// # Examples
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let vec = vec![(5, "hunter2".to_string())];
+  /// let ser = vec.to_json_map_map_values(|v| "*".repeat(v.len())).unwrap();
+  ///
+  /// assert_eq!(ser, "{\"5\":\"*******\"}");
+  /// ```
+  fn to_json_map_map_values<F,W>(self, mut f: F) -> Result<String, serde_json::Error> where
+  F: FnMut(&'a V) -> W,
+  W: Serialize
+  {
+    serde_json::to_string(&crate::map_iter_to_json::SerializeMapIterWrapper {
+      iter: RefCell::new(self.into_iter().map(move |(k, v)| (k, f(v))))
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the entries are written sorted by the native `K: Ord` ordering.
+  ///
+  /// References to the entries are buffered and sorted before serialization, so numeric keys are written as
+  /// `"1","2","10"` rather than in the lexical order of their strings. Entries with equal keys keep their relative order.
+  ///
+  /// # Examples
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let vec = vec![(10, "c"), (1, "a"), (2, "b")];
+  /// let ser = vec.to_json_map_sorted_native().unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"1":"a","2":"b","10":"c"}"#);
+  /// ```
+  fn to_json_map_sorted_native(self) -> Result<String, serde_json::Error> where
+  K: Ord
+  {
+    let mut entries: Vec<&'a (K,V)> = self.into_iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    serde_json::to_string(&SerializeVecIterWrapper {
+      iter: RefCell::new(entries.into_iter())
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but the entries are written in the order given by the comparator `compare`.
+  ///
+  /// References to the entries are buffered and sorted before serialization. Entries which compare equal keep their relative order.
+  ///
+  /// # Examples
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let vec = vec![("bb".to_string(), 1), ("a".to_string(), 2), ("ccc".to_string(), 3)];
+  /// let ser = vec.to_json_map_sorted_by_key(|a, b| a.len().cmp(&b.len())).unwrap();
+  ///
+  /// assert_eq!(ser, r#"{"a":2,"bb":1,"ccc":3}"#);
+  /// ```
+  fn to_json_map_sorted_by_key<F>(self, mut compare: F) -> Result<String, serde_json::Error> where
+  F: FnMut(&K, &K) -> std::cmp::Ordering
+  {
+    let mut entries: Vec<&'a (K,V)> = self.into_iter().collect();
+    entries.sort_by(|a, b| compare(&a.0, &b.0));
+    serde_json::to_string(&SerializeVecIterWrapper {
+      iter: RefCell::new(entries.into_iter())
+    })
+  }
+
+  /// Same as [to_json_map()](#method.to_json_map), but every key must serialize as a JSON number, such as `f64`,
+  /// and is written with exactly `precision` decimals. See [MapIterToJson::to_json_map_float_keys()](trait.MapIterToJson.html#method.to_json_map_float_keys).
+  ///
+  /// # Examples
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let vec = vec![(1.5f64, "foo"), (2.0 / 3.0, "bar")];
+  /// assert_eq!(vec.to_json_map_float_keys(2).unwrap(), r#"{"1.50":"foo","0.67":"bar"}"#);
+  /// ```
+  fn to_json_map_float_keys(self, precision: usize) -> Result<String, serde_json::Error> {
+    crate::MapIterToJson::to_json_map_float_keys(self.into_iter().map(|(k, v)| (k, v)), precision)
+  }
+
+  /// Returns the length in bytes of the string which [to_json_map()](#method.to_json_map) would produce.
+  ///
+  /// The entries are serialized into a sink that only counts bytes, so the output string is never allocated.
+  /// This can be used to preallocate a buffer, or to reject oversized outputs before serializing them.
+  ///
+  /// # Examples
+  /// ```
+  /// use serde_json_any_key::*;
+  ///
+  /// let vec = vec![(5, "foo".to_string())];
+  /// let len = vec.json_map_len().unwrap();
+  ///
+  /// assert_eq!(len, vec.to_json_map().unwrap().len());
+  /// ```
+  fn json_map_len(self) -> Result<usize, serde_json::Error> {
+    let mut counter = crate::serde_with_utils::ByteCounter::default();
+    serde_json::to_writer(&mut counter, &SerializeVecIterWrapper {
+      iter: RefCell::new(self.into_iter())
+    })?;
+    Ok(counter.count)
+  }
 }
 
 impl<'a,K,V,T: IntoIterator<Item=&'a (K,V)>> VecIterToJson<'a,K,V> for T where
@@ -63,6 +213,34 @@ V: 'a + Serialize,
 <Self as IntoIterator>::IntoIter: 'a
 { }
 
+/// Serialize an `IntoIterator<Item=&(K,V)>` as a JSON map into the given `Serializer`, converting the keys as
+/// [to_json_map()](trait.VecIterToJson.html#method.to_json_map) does.
+///
+/// This is the same serialization that [any_key_vec](any_key_vec/index.html) performs for a `#[serde(with)]` field.
+/// See [serialize_map_into()](fn.serialize_map_into.html) for details.
+///
+/// # Examples
+/// ```
+/// use serde_json_any_key::*;
+///
+/// let vec = vec![((3, 5), 6), ((7, 9), 10)];
+///
+/// let mut buf = Vec::new();
+/// serialize_vec_into(&vec, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+///
+/// assert_eq!(String::from_utf8(buf).unwrap(), r#"{"[3,5]":6,"[7,9]":10}"#);
+/// ```
+pub fn serialize_vec_into<'a,S,I,K,V>(iter: I, serializer: S) -> Result<S::Ok, S::Error> where
+  S: Serializer,
+  I: IntoIterator<Item=&'a (K,V)>,
+  K: 'a + Serialize + Any,
+  V: 'a + Serialize
+{
+  SerializeVecIterWrapper {
+    iter: RefCell::new(iter.into_iter())
+  }.serialize(serializer)
+}
+
 pub(crate) struct SerializeVecIterWrapper<'a,K,V,I> where 
 I: Iterator<Item=&'a (K,V)>,
 K: 'a,
@@ -79,24 +257,10 @@ impl<'a,K,V,I> Serialize for SerializeVecIterWrapper<'a,K,V,I> where
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
     S: Serializer
   {
-    let mut ser_map = serializer.serialize_map(None)?;
-    let mut iter = self.iter.borrow_mut();
-    // handle strings specially so they don't get escaped and wrapped inside another string
-    // compiler seems to be able to optimize this branch away statically
-    if TypeId::of::<K>() == TypeId::of::<String>() {
-      while let Some((k, v)) = iter.next() {
-        let s = (k as &dyn Any).downcast_ref::<String>().ok_or(S::Error::custom("Failed to serialize String as string"))?;
-        ser_map.serialize_entry(s, &v)?;
-      }
-    } else {
-      while let Some((k, v)) = iter.next() {
-        ser_map.serialize_entry(match &serde_json::to_string(&k)
-        {
-          Ok(key_string) => key_string,
-          Err(e) => { return Err(e).map_err(S::Error::custom); }
-        }, &v)?;
-      }
-    }
+    // a Serialize impl which re-enters this wrapper would otherwise panic on the second borrow
+    let mut iter = self.iter.try_borrow_mut().map_err(|_| S::Error::custom("iterator is already being serialized"))?;
+    let mut ser_map = serializer.serialize_map(crate::serde_with_utils::len_hint(&*iter))?;
+    crate::serde_with_utils::serialize_map_entries(&mut ser_map, iter.by_ref().map(|(k, v)| (k, v)))?;
     ser_map.end()
   }
 }
@@ -1,3 +1,4 @@
+#![allow(clippy::redundant_field_names, clippy::vec_init_then_push, clippy::useless_vec)]
 
 #[cfg(test)]
 mod tests {
@@ -350,4 +351,2251 @@ mod tests {
     let serialized = btree.to_json_map().unwrap();
     assert_eq!(serialized, canonical_serialization);
   }
+
+  #[test]
+  fn test_key_parser_map() {
+    let ser = "{\"3;5\":{\"a\":7,\"b\":9},\"11;12\":{\"a\":13,\"b\":14}}";
+    let parse = |key: &str| -> Result<Test, String> {
+      let (a, b) = key.split_once(';').ok_or(format!("bad key {}", key))?;
+      Ok(Test { a: a.parse().map_err(|_| "bad a")?, b: b.parse().map_err(|_| "bad b")? })
+    };
+    let deser: HashMap<Test, Test> = json_to_map_with_key_parser(ser, parse).unwrap();
+    let mut expected = HashMap::<Test, Test>::new();
+    expected.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    expected.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    assert_eq!(expected, deser);
+
+    let err = json_to_map_with_key_parser::<Test, Test, _, _>("{\"35\":{\"a\":7,\"b\":9}}", parse).unwrap_err();
+    assert_eq!(err.to_string(), "invalid key \"35\": bad key 35");
+  }
+
+  #[test]
+  fn test_float_roundtrip_vec() {
+    let expected = ["5.0", "-0.0", "0.1", "1e+300", "-1.7976931348623157e+308", "5e-324"];
+    for (f, key) in [5.0f64, -0.0, 0.1, 1e300, -1.7976931348623157e308, 5e-324].into_iter().zip(expected) {
+      let data = vec![(f, 1)];
+      let serialized = data.to_json_map().unwrap();
+      assert_eq!(serialized, format!("{{\"{}\":1}}", key));
+      let deser: Vec<(f64, i32)> = json_to_vec(&serialized).unwrap();
+      assert_eq!(f.to_bits(), deser[0].0.to_bits());
+    }
+
+    for f in [5.0f32, -0.0, 3.4028235e38] {
+      let data = [(f, 1)];
+      let serialized = data.iter().to_json_map().unwrap();
+      let deser: Vec<(f32, i32)> = json_to_vec(&serialized).unwrap();
+      assert_eq!(f.to_bits(), deser[0].0.to_bits());
+    }
+  }
+
+  #[test]
+  fn test_float_key_normalization() {
+    // keys written by other producers may omit the fractional part or use an exponent
+    for key in ["5", "5.0", "5e0", "0.5e1"] {
+      let deser: Vec<(f64, i32)> = json_to_vec(format!("{{\"{}\":1}}", key)).unwrap();
+      assert_eq!(deser, vec![(5.0, 1)]);
+    }
+    let deser: Vec<(f64, i32)> = json_to_vec("{\"-0\":1}").unwrap();
+    assert!(deser[0].0 == 0.0 && deser[0].0.is_sign_negative());
+  }
+
+  #[test]
+  fn test_float_non_finite_key() {
+    for f in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+      let data = vec![(f, 1)];
+      assert_eq!(data.to_json_map().unwrap_err().to_string(), "float key must be finite");
+      assert!(data.into_json_map().is_err());
+    }
+    let data = [(f32::NAN, 1)];
+    assert!(data.iter().map(|(k, v)| (k, v)).to_json_map().is_err());
+  }
+
+  #[test]
+  fn test_copy_roundtrip_copied_iter() {
+    let data = [(Test {a: 3, b: 5}, Test {a: 7, b: 9}), (Test {a: 11, b: 12}, Test {a: 13, b: 14})];
+    let ser1 = data.iter().copied().into_json_map().unwrap();
+    let ser2 = data.iter().copied().into_json_map().unwrap();
+    assert_eq!(ser1, ser2);
+    assert_eq!(ser1, data.to_json_map().unwrap());
+    let mut deser: Vec<(Test, Test)> = json_to_vec(&ser1).unwrap();
+    deser.sort();
+    assert_eq!(data.to_vec(), deser);
+  }
+
+  #[test]
+  fn test_struct_roundtrip_arc_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+    let deser: std::sync::Arc<HashMap<Test, Test>> = json_to_arc_map(&serialized).unwrap();
+
+    assert_eq!(data, *deser);
+  }
+
+  #[test]
+  fn test_path_roundtrip_map() {
+    let path = std::path::Path::new("cache").join("nested dir").join("file.json");
+    let mut data = HashMap::<std::path::PathBuf, i32>::new();
+    data.insert(path.clone(), 5);
+    let serialized = data.to_json_map().unwrap();
+    // keys are bare strings, the same as a HashMap<String,i32>
+    let mut string_map = HashMap::<String, i32>::new();
+    string_map.insert(path.to_str().unwrap().to_string(), 5);
+    assert_eq!(serialized, serde_json::to_string(&string_map).unwrap());
+
+    let deser: HashMap<std::path::PathBuf, i32> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+    let deser: Vec<(std::path::PathBuf, i32)> = json_to_vec(&serialized).unwrap();
+    assert_eq!(deser, vec![(path, 5)]);
+  }
+
+  #[test]
+  fn test_path_keys_written_by_2_0_0() {
+    // 2.0.0 wrote string-like keys other than String with serde_json::to_string()
+    let mut legacy = HashMap::<String, i32>::new();
+    legacy.insert(serde_json::to_string(&std::path::PathBuf::from("/tmp/a")).unwrap(), 5);
+    let serialized = serde_json::to_string(&legacy).unwrap();
+    assert_eq!(serialized, r#"{"\"/tmp/a\"":5}"#);
+    let deser: HashMap<std::path::PathBuf, i32> = json_to_map(&serialized).unwrap();
+    assert_eq!(deser.keys().collect::<Vec<_>>(), vec![std::path::Path::new("/tmp/a")]);
+
+    let os = std::ffi::OsString::from("foo/bar");
+    let legacy = vec![(serde_json::to_string(&os).unwrap(), 6)];
+    let deser: Vec<(std::ffi::OsString, i32)> = json_to_vec(legacy.to_json_map().unwrap()).unwrap();
+    assert_eq!(deser, vec![(os, 6)]);
+
+    // bare keys which only look like the old forms are kept as they are
+    let deser: HashMap<std::path::PathBuf, i32> = json_to_map(r#"{"\"unterminated":1,"{not json}":2}"#).unwrap();
+    assert!(deser.contains_key(std::path::Path::new("\"unterminated")));
+    assert!(deser.contains_key(std::path::Path::new("{not json}")));
+    let deser: Vec<(std::ffi::OsString, i32)> = json_to_vec(r#"{"{not json}":2}"#).unwrap();
+    assert_eq!(deser, vec![(std::ffi::OsString::from("{not json}"), 2)]);
+  }
+
+  #[test]
+  fn test_os_string_roundtrip_vec() {
+    let data = vec![(std::ffi::OsString::from("foo/bar baz"), 5)];
+    let serialized = data.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"foo/bar baz\":5}");
+    let deser: Vec<(std::ffi::OsString, i32)> = json_to_vec(&serialized).unwrap();
+    assert_eq!(data, deser);
+    let deser: Vec<(std::ffi::OsString, i32)> = json_to_iter(&serialized).unwrap().map(|x| x.unwrap()).collect();
+    assert_eq!(data, deser);
+  }
+
+  #[test]
+  fn test_json_map_len() {
+    let mut data = HashMap::<Test, String>::new();
+    data.insert(Test {a: 3, b: 5}, "foo".to_string());
+    data.insert(Test {a: 11, b: 12}, "bar \"baz\"".to_string());
+    assert_eq!(data.json_map_len().unwrap(), data.to_json_map().unwrap().len());
+    assert_eq!(data.iter().json_map_len().unwrap(), data.to_json_map().unwrap().len());
+
+    let vec = vec![("foo\n".to_string(), 5), ("bar".to_string(), 6)];
+    assert_eq!(vec.json_map_len().unwrap(), vec.to_json_map().unwrap().len());
+
+    let empty = Vec::<(i32, i32)>::new();
+    assert_eq!(empty.json_map_len().unwrap(), 2);
+    assert!(vec![(f64::NAN, 1)].json_map_len().is_err());
+  }
+
+  // Mirrors serde_bytes::ByteBuf, which uses serialize_bytes / deserialize_byte_buf
+  #[derive(Clone, PartialEq, Eq, Debug)]
+  struct ByteBuf(Vec<u8>);
+
+  impl Serialize for ByteBuf {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      serializer.serialize_bytes(&self.0)
+    }
+  }
+
+  impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      struct ByteBufVisitor;
+      impl<'de> serde::de::Visitor<'de> for ByteBufVisitor {
+        type Value = ByteBuf;
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+          write!(formatter, "byte array")
+        }
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+          let mut bytes = Vec::new();
+          while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+          }
+          Ok(ByteBuf(bytes))
+        }
+        fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<ByteBuf, E> {
+          Ok(ByteBuf(v.to_vec()))
+        }
+        fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+          Ok(ByteBuf(v))
+        }
+      }
+      deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+  }
+
+  #[test]
+  fn test_bytes_roundtrip_map() {
+    let mut data = HashMap::<Test, Vec<u8>>::new();
+    data.insert(Test {a: 3, b: 5}, vec![0, 1, 255]);
+    data.insert(Test {a: 11, b: 12}, vec![]);
+    let serialized = data.to_json_map().unwrap();
+    let deser: HashMap<Test, Vec<u8>> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+
+    let mut data = HashMap::<Test, ByteBuf>::new();
+    data.insert(Test {a: 3, b: 5}, ByteBuf(vec![0, 1, 255]));
+    data.insert(Test {a: 11, b: 12}, ByteBuf(vec![]));
+    let serialized = data.to_json_map().unwrap();
+    let deser: HashMap<Test, ByteBuf> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+    let mut deser: Vec<(Test, ByteBuf)> = json_to_iter(&serialized).unwrap().map(|x| x.unwrap()).collect();
+    deser.sort_by_key(|x| x.0);
+    assert_eq!(deser, vec![(Test {a: 3, b: 5}, ByteBuf(vec![0, 1, 255])), (Test {a: 11, b: 12}, ByteBuf(vec![]))]);
+  }
+
+  #[test]
+  fn test_bytes_serde_with_map() {
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithMap {
+      #[serde(with = "any_key_map")]
+      pub inner: HashMap<Test, ByteBuf>
+    }
+    let mut data = SerdeWithMap { inner: HashMap::new() };
+    data.inner.insert(Test {a: 3, b: 5}, ByteBuf(vec![0, 1, 255]));
+    let serialized = serde_json::to_string(&data).unwrap();
+    assert_eq!(serialized, "{\"inner\":{\"{\\\"a\\\":3,\\\"b\\\":5}\":[0,1,255]}}");
+    let deser: SerdeWithMap = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(data, deser);
+  }
+
+  #[test]
+  fn test_struct_roundtrip_vec_all() {
+    let data = vec![
+      (Test {a: 11, b: 12}, Test {a: 13, b: 14}),
+      (Test {a: 3, b: 5}, Test {a: 7, b: 9}),
+      (Test {a: 11, b: 12}, Test {a: 15, b: 16}),
+    ];
+    let serialized = data.to_json_map().unwrap();
+    let deser: Vec<(Test, Test)> = json_to_vec_all(&serialized).unwrap();
+    assert_eq!(data, deser);
+
+    let deser: Vec<(String, i32)> = json_to_vec_all("{\"foo\":1,\"bar\":2,\"foo\":3}").unwrap();
+    assert_eq!(deser, vec![("foo".to_string(), 1), ("bar".to_string(), 2), ("foo".to_string(), 3)]);
+
+    assert!(json_to_vec_all::<i32, i32>("[1,2]").is_err());
+    assert!(json_to_vec_all::<i32, i32>("{\"1\":2} trailing").is_err());
+  }
+
+  #[test]
+  fn test_struct_filtered_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map_filtered(|k, _v| k.a == 3).unwrap();
+    assert_eq!(serialized, "{\"{\\\"a\\\":3,\\\"b\\\":5}\":{\"a\":7,\"b\":9}}");
+    let serialized = data.iter().to_json_map_filtered(|_k, v| v.b == 14).unwrap();
+    let deser: HashMap<Test, Test> = json_to_map(&serialized).unwrap();
+    assert_eq!(deser.len(), 1);
+    assert_eq!(deser[&Test {a: 11, b: 12}], Test {a: 13, b: 14});
+    assert_eq!(data.to_json_map_filtered(|_k, _v| false).unwrap(), "{}");
+  }
+
+  #[test]
+  fn test_string_filtered_vec() {
+    let data = vec![("foo".to_string(), 5), ("bar".to_string(), 0)];
+    let serialized = data.to_json_map_filtered(|_k, v| *v != 0).unwrap();
+    assert_eq!(serialized, "{\"foo\":5}");
+    let serialized = data.iter().to_json_map_filtered(|k, _v| k.starts_with('b')).unwrap();
+    assert_eq!(serialized, "{\"bar\":0}");
+  }
+
+  #[test]
+  fn test_struct_map_with_default() {
+    #[derive(Clone, Default, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct Value {
+      pub a: i32
+    }
+    let ser = "{\"{\\\"a\\\":3,\\\"b\\\":5}\":{\"a\":7},\"{\\\"a\\\":11,\\\"b\\\":12}\":{\"a\":\"new\"}}";
+    let (deser, fallbacks): (HashMap<Test, Value>, Vec<Test>) = json_to_map_with_default(ser).unwrap();
+    assert_eq!(deser[&Test {a: 3, b: 5}], Value {a: 7});
+    assert_eq!(deser[&Test {a: 11, b: 12}], Value::default());
+    assert_eq!(fallbacks, vec![Test {a: 11, b: 12}]);
+
+    // keys must still be valid
+    let ser = "{\"not a key\":{\"a\":7}}";
+    assert!(json_to_map_with_default::<Test, Value>(ser).is_err());
+  }
+
+  #[test]
+  fn test_struct_roundtrip_ref_key_map() {
+    let keys = [Test {a: 3, b: 5}, Test {a: 11, b: 12}];
+    let mut data = HashMap::<&Test, Test>::new();
+    data.insert(&keys[0], Test {a: 7, b: 9});
+    data.insert(&keys[1], Test {a: 13, b: 14});
+    let serialized = data.ref_keys_to_json_map().unwrap();
+    assert_eq!(serialized, data.iter().ref_keys_to_json_map().unwrap());
+    let deser: HashMap<Test, Test> = json_to_map(&serialized).unwrap();
+    let owned: HashMap<Test, Test> = data.iter().map(|(k, v)| (**k, *v)).collect();
+    assert_eq!(owned, deser);
+
+    let names = ["foo".to_string()];
+    let mut data = std::collections::BTreeMap::<&String, i32>::new();
+    data.insert(&names[0], 5);
+    assert_eq!(data.ref_keys_to_json_map().unwrap(), "{\"foo\":5}");
+  }
+
+  #[test]
+  fn test_key_and_value_errors_are_distinct() {
+    let bad_key = "{\"{\\\"a\\\":3}\":{\"a\":7,\"b\":9}}";
+    let bad_value = "{\"{\\\"a\\\":3,\\\"b\\\":5}\":{\"a\":7}}";
+
+    let err = json_to_map::<Test, Test>(bad_key).unwrap_err().to_string();
+    assert!(err.starts_with("invalid key \"{\\\"a\\\":3}\": missing field `b`"), "{}", err);
+    let err = json_to_map::<Test, Test>(bad_value).unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key \"{\\\"a\\\":3,\\\"b\\\":5}\": missing field `b`"), "{}", err);
+
+    let err = json_to_vec::<Test, Test>(bad_key).unwrap_err().to_string();
+    assert!(err.starts_with("invalid key "), "{}", err);
+    let err = json_to_vec::<Test, Test>(bad_value).unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key "), "{}", err);
+
+    let err = json_to_iter::<Test, Test>(bad_key).unwrap().next().unwrap().unwrap_err().to_string();
+    assert!(err.starts_with("invalid key "), "{}", err);
+    let err = json_to_iter::<Test, Test>(bad_value).unwrap().next().unwrap().unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key "), "{}", err);
+
+    // the streaming paths report entries in the same way
+    let err = json_to_vec_all::<Test, Test>(bad_key).unwrap_err().to_string();
+    assert!(err.starts_with("invalid key "), "{}", err);
+    let err = json_to_vec_all::<Test, Test>(bad_value).unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key \"{\\\"a\\\":3,\\\"b\\\":5}\": missing field `b`"), "{}", err);
+
+    let err = json_to_btreemap::<Test, Test>(bad_key).unwrap_err().to_string();
+    assert!(err.starts_with("invalid key "), "{}", err);
+    let err = json_to_btreemap::<Test, Test>(bad_value).unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key "), "{}", err);
+
+    let err = json_to_iter_streaming::<_, Test, Test>(bad_key.as_bytes()).next().unwrap().unwrap_err().to_string();
+    assert!(err.starts_with("invalid key "), "{}", err);
+    let err = json_to_iter_streaming::<_, Test, Test>(bad_value.as_bytes()).next().unwrap().unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key "), "{}", err);
+
+    // the position within the key is not reported, as it would be mistaken for a position in the document
+    let err = json_to_map::<i32, i32>("{\"five\":5}").unwrap_err().to_string();
+    assert_eq!(err, "invalid key \"five\": expected ident");
+  }
+
+  #[test]
+  fn test_struct_roundtrip_writer() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let mut writer = JsonMapWriter::new(Vec::new());
+    for (k, v) in data.iter() {
+      writer.write_entry(k, v).unwrap();
+    }
+    let serialized = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert_eq!(serialized, data.to_json_map().unwrap());
+    let deser: HashMap<Test, Test> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+
+    let mut writer = JsonMapWriter::new(Vec::new());
+    writer.write_entry(&"foo \"bar\"".to_string(), "baz").unwrap();
+    writer.write_entry(&std::path::PathBuf::from("qux"), &[1, 2]).unwrap();
+    assert_eq!(writer.finish().unwrap(), b"{\"foo \\\"bar\\\"\":\"baz\",\"qux\":[1,2]}");
+
+    let writer = JsonMapWriter::new(Vec::new());
+    assert_eq!(writer.finish().unwrap(), b"{}");
+  }
+
+  #[test]
+  fn test_struct_roundtrip_reader() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+    let deser: HashMap<Test, Test> = JsonMapReader::new(serialized.as_bytes()).map(|x| x.unwrap()).collect();
+    assert_eq!(data, deser);
+
+    let pretty = "\n{ \"foo\" : [1, {\"}\": \"]\"}] ,\n \"b\\\"ar\":-1.5e3,\"foo\":null,\"t\":true }";
+    let deser: Vec<(String, serde_json::Value)> = JsonMapReader::new(pretty.as_bytes()).map(|x| x.unwrap()).collect();
+    assert_eq!(deser, vec![
+      ("foo".to_string(), serde_json::json!([1, {"}": "]"}])),
+      ("b\"ar".to_string(), serde_json::from_str::<serde_json::Value>("-1.5e3").unwrap()),
+      ("foo".to_string(), serde_json::Value::Null),
+      ("t".to_string(), serde_json::Value::Bool(true)),
+    ]);
+
+    let deser: Vec<(i32, i32)> = JsonMapReader::new("{}".as_bytes()).map(|x| x.unwrap()).collect();
+    assert!(deser.is_empty());
+  }
+
+  #[test]
+  fn test_reader_errors() {
+    for bad in ["[1]", "{\"1\":2", "{\"1\" 2}", "{\"1\":2 \"3\":4}", "{1:2}", "{\"1\":tru}"] {
+      let mut reader = JsonMapReader::<_, i32, bool>::new(bad.as_bytes());
+      let results: Vec<_> = reader.by_ref().collect();
+      assert!(results.last().unwrap().is_err(), "{}", bad);
+      assert!(reader.next().is_none());
+    }
+    let err = JsonMapReader::<_, i32, i32>::new("{\"x\":1}".as_bytes()).next().unwrap().unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"x\": "));
+    let err = JsonMapReader::<_, i32, i32>::new("{\"1\":\"x\"}".as_bytes()).next().unwrap().unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key \"1\": "));
+  }
+
+  #[test]
+  fn test_bool_roundtrip_map() {
+    let mut data = HashMap::<bool, Test>::new();
+    data.insert(true, Test {a: 3, b: 5});
+    data.insert(false, Test {a: 7, b: 9});
+    let serialized = data.to_json_map().unwrap();
+    let deser: HashMap<bool, Test> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+
+    let vec = vec![(true, 1)];
+    assert_eq!(vec.to_json_map().unwrap(), "{\"true\":1}");
+
+    // a bool key and a String key "true" are indistinguishable once serialized
+    let strings = vec![("true".to_string(), 1)];
+    assert_eq!(vec.to_json_map().unwrap(), strings.to_json_map().unwrap());
+    let deser: Vec<(String, i32)> = json_to_vec(vec.to_json_map().unwrap()).unwrap();
+    assert_eq!(deser, strings);
+    let deser: Vec<(bool, i32)> = json_to_vec(strings.to_json_map().unwrap()).unwrap();
+    assert_eq!(deser, vec);
+  }
+
+  #[test]
+  fn test_null_literal_keys() {
+    let vec = vec![((), 1)];
+    let serialized = vec.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"null\":1}");
+    let deser: Vec<((), i32)> = json_to_vec(&serialized).unwrap();
+    assert_eq!(deser, vec);
+    assert!(json_to_vec::<bool, i32>(&serialized).is_err());
+  }
+
+  #[test]
+  fn test_unsized_values_map_iter() {
+    let mut data = HashMap::<i32, &str>::new();
+    data.insert(5, "foo");
+    data.insert(6, "bar");
+    let serialized = data.iter().to_json_map().unwrap();
+    let deser: HashMap<i32, String> = json_to_map(&serialized).unwrap();
+    assert_eq!(deser[&5], "foo");
+    assert_eq!(deser[&6], "bar");
+
+    // V = str and V = [i32], both unsized
+    let mut owned = HashMap::<i32, String>::new();
+    owned.insert(5, "foo".to_string());
+    let serialized = owned.iter().map(|(k, v)| (k, v.as_str())).to_json_map().unwrap();
+    assert_eq!(serialized, "{\"5\":\"foo\"}");
+    let mut slices = std::collections::BTreeMap::<i32, Vec<i32>>::new();
+    slices.insert(5, vec![1, 2]);
+    let serialized = slices.iter().map(|(k, v)| (k, v.as_slice())).to_json_map().unwrap();
+    assert_eq!(serialized, "{\"5\":[1,2]}");
+  }
+
+  #[test]
+  fn test_struct_for_each() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+    let mut deser = HashMap::<Test, Test>::new();
+    json_for_each(&serialized, |(k, v)| { deser.insert(k, v); Ok(()) }).unwrap();
+    assert_eq!(data, deser);
+
+    // stops at the first callback error
+    let mut calls = 0;
+    let err = json_for_each(&serialized, |(_k, _v): (Test, Test)| {
+      calls += 1;
+      Err(serde::de::Error::custom("sink full"))
+    }).unwrap_err();
+    assert_eq!(err.to_string(), "sink full");
+    assert_eq!(calls, 1);
+
+    assert!(json_for_each("{\"x\":1}", |(_k, _v): (i32, i32)| Ok(())).is_err());
+  }
+
+  #[cfg(feature = "arbitrary_precision")]
+  #[test]
+  fn test_arbitrary_precision_number_roundtrip() {
+    let precise = "12345678901234567890.123456789012345678901";
+    let num: serde_json::Number = serde_json::from_str(precise).unwrap();
+    let data = vec![(num.clone(), num.clone())];
+    let serialized = data.to_json_map().unwrap();
+    // the key is written with exactly the same digits as the value
+    assert_eq!(serialized, format!("{{\"{}\":{}}}", precise, precise));
+
+    let deser: Vec<(serde_json::Number, serde_json::Number)> = json_to_vec(&serialized).unwrap();
+    assert_eq!(deser, data);
+    let deser: HashMap<String, serde_json::Number> = json_to_map(&serialized).unwrap();
+    assert_eq!(deser[precise].to_string(), precise);
+    let deser: Vec<(serde_json::Number, serde_json::Number)> = JsonMapReader::new(serialized.as_bytes()).map(|x| x.unwrap()).collect();
+    assert_eq!(deser, data);
+  }
+
+  #[test]
+  fn test_struct_roundtrip_map_to_iter() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+    let map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&serialized).unwrap();
+    let deser: HashMap<Test, Test> = map_to_iter(map).map(|x| x.unwrap()).collect();
+    assert_eq!(data, deser);
+
+    let mut map = serde_json::Map::new();
+    map.insert("foo".to_string(), serde_json::Value::from(5));
+    let deser: Vec<(String, i32)> = map_to_iter(map).map(|x| x.unwrap()).collect();
+    assert_eq!(deser, vec![("foo".to_string(), 5)]);
+  }
+
+  #[test]
+  fn test_struct_map_values() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map_map_values(|v| v.a + v.b).unwrap();
+    let deser: HashMap<Test, i32> = json_to_map(&serialized).unwrap();
+    assert_eq!(deser[&Test {a: 3, b: 5}], 16);
+    assert_eq!(deser[&Test {a: 11, b: 12}], 27);
+    // the original map is untouched
+    assert_eq!(data[&Test {a: 3, b: 5}], Test {a: 7, b: 9});
+
+    let vec = vec![("password".to_string(), "hunter2".to_string()), ("user".to_string(), "bob".to_string())];
+    let serialized = vec.iter().to_json_map_map_values(|v| if v == "hunter2" { "<redacted>" } else { v.as_str() }).unwrap();
+    assert_eq!(serialized, "{\"password\":\"<redacted>\",\"user\":\"bob\"}");
+  }
+
+  #[test]
+  fn test_struct_consuming_wrapper() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let expected = data.to_json_map().unwrap();
+    let wrap = SerializeConsumingIterWrapper::new(data.clone());
+    let serialized = serde_json::to_string(&wrap).unwrap();
+    let deser: HashMap<Test, Test> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+    assert_eq!(serialized.len(), expected.len());
+    // the iterator has been consumed
+    assert_eq!(serde_json::to_string(&wrap).unwrap(), "{}");
+
+    let pretty = serde_json::to_string_pretty(&SerializeConsumingIterWrapper::new(vec![(5, "foo")])).unwrap();
+    assert_eq!(pretty, "{\n  \"5\": \"foo\"\n}");
+  }
+
+  #[test]
+  fn test_struct_map_with_key_conversion() {
+    let mut data = HashMap::<Test, i32>::new();
+    data.insert(Test {a: 3, b: 5}, 1);
+    data.insert(Test {a: 11, b: 12}, 2);
+    let serialized = data.to_json_map().unwrap();
+    let deser: HashMap<(i32, i32), i32> = json_to_map_with(&serialized, |k: Test| (k.a, k.b)).unwrap();
+    assert_eq!(deser.len(), 2);
+    assert_eq!(deser[&(3, 5)], 1);
+    assert_eq!(deser[&(11, 12)], 2);
+
+    // keys that collide after conversion keep only one value
+    let deser: HashMap<bool, i32> = json_to_map_with(&serialized, |k: Test| k.a > 0).unwrap();
+    assert_eq!(deser.len(), 1);
+  }
+
+  #[test]
+  fn test_struct_btreemap_stable_output() {
+    let mut data = std::collections::BTreeMap::<Test, i32>::new();
+    data.insert(Test {a: 10, b: 1}, 4);
+    data.insert(Test {a: 9, b: 2}, 3);
+    data.insert(Test {a: 9, b: -1}, 2);
+    data.insert(Test {a: -5, b: 0}, 1);
+    let expected = "{\"{\\\"a\\\":-5,\\\"b\\\":0}\":1,\"{\\\"a\\\":9,\\\"b\\\":-1}\":2,\"{\\\"a\\\":9,\\\"b\\\":2}\":3,\"{\\\"a\\\":10,\\\"b\\\":1}\":4}";
+    assert_eq!(data.to_json_map().unwrap(), expected);
+    assert_eq!(data.iter().to_json_map().unwrap(), expected);
+    assert_eq!(data.clone().into_json_map().unwrap(), expected);
+
+    // inserting in a different order produces the same output
+    let reversed: std::collections::BTreeMap<Test, i32> = data.iter().rev().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(reversed.to_json_map().unwrap(), expected);
+  }
+
+  #[test]
+  fn test_struct_map_nested_value() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    // double-encode every value
+    let serialized = data.to_json_map_map_values(|v| serde_json::to_string(v).unwrap()).unwrap();
+    let deser: HashMap<Test, Test> = json_to_map_nested_value(&serialized).unwrap();
+    assert_eq!(data, deser);
+    // regular values are unaffected
+    let deser: HashMap<Test, Test> = json_to_map_nested_value(&data.to_json_map().unwrap()).unwrap();
+    assert_eq!(data, deser);
+
+    let err = json_to_map_nested_value::<i32, Test>("{\"5\":\"not json\"}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key \"5\": "));
+  }
+
+  #[test]
+  fn test_struct_with_capacity() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let len = data.json_map_len().unwrap();
+    let serialized = data.to_json_map_with_capacity(len).unwrap();
+    assert_eq!(serialized, data.to_json_map().unwrap());
+    assert_eq!(serialized.capacity(), len);
+
+    let vec = vec![("foo".to_string(), 5)];
+    assert_eq!(vec.to_json_map_with_capacity(0).unwrap(), "{\"foo\":5}");
+    assert_eq!(vec.iter().to_json_map_with_capacity(4096).unwrap().capacity(), 4096);
+  }
+
+  // Serializes as "a.b" for human-readable formats, and as a tuple otherwise
+  #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+  struct Version {
+    pub major: u8,
+    pub minor: u8
+  }
+
+  impl Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      if serializer.is_human_readable() {
+        serializer.serialize_str(&format!("{}.{}", self.major, self.minor))
+      } else {
+        (self.major, self.minor).serialize(serializer)
+      }
+    }
+  }
+
+  impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      if deserializer.is_human_readable() {
+        let s = String::deserialize(deserializer)?;
+        let (major, minor) = s.split_once('.').ok_or(serde::de::Error::custom("expected major.minor"))?;
+        Ok(Version {
+          major: major.parse().map_err(serde::de::Error::custom)?,
+          minor: minor.parse().map_err(serde::de::Error::custom)?
+        })
+      } else {
+        let (major, minor) = <(u8, u8)>::deserialize(deserializer)?;
+        Ok(Version { major, minor })
+      }
+    }
+  }
+
+  #[test]
+  fn test_human_readable_keys() {
+    let mut data = HashMap::<Version, i32>::new();
+    data.insert(Version {major: 1, minor: 2}, 5);
+    let serialized = data.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"\\\"1.2\\\"\":5}");
+    let deser: HashMap<Version, i32> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+    let deser: Vec<(Version, i32)> = json_to_vec_all(&serialized).unwrap();
+    assert_eq!(deser, vec![(Version {major: 1, minor: 2}, 5)]);
+
+    let mut writer = JsonMapWriter::new(Vec::new());
+    writer.write_entry(&Version {major: 1, minor: 2}, &5).unwrap();
+    assert_eq!(String::from_utf8(writer.finish().unwrap()).unwrap(), serialized);
+  }
+
+  #[test]
+  fn test_struct_map_with_raw() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+    let (deser, raw): (HashMap<Test, Test>, _) = json_to_map_with_raw(&serialized).unwrap();
+    assert_eq!(deser, json_to_map::<Test, Test>(&serialized).unwrap());
+    assert_eq!(raw, serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&serialized).unwrap());
+    assert_eq!(raw["{\"a\":3,\"b\":5}"]["a"], 7);
+  }
+
+  #[test]
+  fn test_struct_guards_to_json_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let locked: Vec<std::sync::RwLock<(Test, Test)>> = data.iter().map(|(k, v)| std::sync::RwLock::new((*k, *v))).collect();
+    let serialized = guards_to_json_map(locked.iter().map(|l| l.read().unwrap()), |g| (&g.0, &g.1)).unwrap();
+    let deser: HashMap<Test, Test> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+
+    // guards are released as soon as their entry has been written
+    let serialized = guards_to_json_map(locked.iter().map(|l| l.write().unwrap()), |g| (&g.0, &g.1)).unwrap();
+    assert_eq!(json_to_map::<Test, Test>(&serialized).unwrap(), data);
+
+    let strings = vec![std::sync::Mutex::new(("foo".to_string(), "bar".to_string()))];
+    let serialized = guards_to_json_map(strings.iter().map(|m| m.lock().unwrap()), |g| (&g.0, g.1.as_str())).unwrap();
+    assert_eq!(serialized, "{\"foo\":\"bar\"}");
+  }
+
+  #[test]
+  fn test_guards_to_json_map_is_not_a_snapshot() {
+    let locked: Vec<std::sync::RwLock<(Test, i32)>> = (0..3).map(|i| std::sync::RwLock::new((Test {a: i, b: i}, 0))).collect();
+    let serialized = guards_to_json_map(locked.iter().enumerate().map(|(i, l)| {
+      // while entry 1 is reached, an earlier and a later entry are updated
+      if i == 1 {
+        locked[0].write().unwrap().1 = 10;
+        locked[2].write().unwrap().1 = 20;
+      }
+      l.read().unwrap()
+    }), |g| (&g.0, &g.1)).unwrap();
+    let deser: Vec<(Test, i32)> = json_to_vec(&serialized).unwrap();
+    assert_eq!(deser, vec![(Test {a: 0, b: 0}, 0), (Test {a: 1, b: 1}, 0), (Test {a: 2, b: 2}, 20)]);
+  }
+
+
+  #[test]
+  fn test_struct_from_json_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+
+    let deser: HashMap<Test, Test> = serialized.from_json_map().unwrap();
+    assert_eq!(data, deser);
+    assert_eq!(HashMap::<Test, Test>::from_json_map(&serialized).unwrap(), data);
+    assert_eq!(serialized.as_str().from_json_map::<HashMap<Test, Test>>().unwrap(), data);
+
+    let btree: std::collections::BTreeMap<(i32, i32), i32> = "{\"[10,0]\":1,\"[9,0]\":2}".from_json_map().unwrap();
+    assert_eq!(btree.into_iter().collect::<Vec<_>>(), vec![((9, 0), 2), ((10, 0), 1)]);
+
+    let err = HashMap::<Test, Test>::from_json_map("[]").unwrap_err();
+    assert_eq!(err.to_string(), "Value is not a JSON map");
+  }
+
+
+  #[test]
+  fn test_struct_prefixed() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map_prefixed("tenant1:").unwrap();
+    let raw: HashMap<String, Test> = serde_json::from_str(&serialized).unwrap();
+    assert!(raw.keys().all(|k| k.starts_with("tenant1:{")));
+
+    let deser: HashMap<Test, Test> = json_to_map_prefixed(&serialized, "tenant1:").unwrap();
+    assert_eq!(data, deser);
+
+    let err = json_to_map_prefixed::<i32, i32>("{\"t2:5\":6}", "t1:").unwrap_err();
+    assert_eq!(err.to_string(), "invalid key \"t2:5\": missing prefix \"t1:\"");
+
+    let mut strings = HashMap::<String, i32>::new();
+    strings.insert("foo".to_string(), 1);
+    assert_eq!(strings.to_json_map_prefixed("").unwrap(), strings.to_json_map().unwrap());
+    assert_eq!(json_to_map_prefixed::<String, i32>("{\"t:foo\":1}", "t:").unwrap(), strings);
+  }
+
+
+  #[test]
+  fn test_struct_json_map_lines() {
+    let mut log = String::new();
+    let mut expected = vec![];
+    for i in 0..3 {
+      let mut data = HashMap::<Test, Test>::new();
+      data.insert(Test {a: i, b: i + 1}, Test {a: 7, b: 9});
+      let line = data.to_json_map_line().unwrap();
+      assert!(line.ends_with('\n'));
+      assert_eq!(line.matches('\n').count(), 1);
+      log.push_str(&line);
+      expected.push(data);
+    }
+    let maps: Vec<HashMap<Test, Test>> = json_map_lines(&log).collect::<Result<_, _>>().unwrap();
+    assert_eq!(maps, expected);
+
+    // blank lines and CRLF endings are tolerated, and an invalid line does not stop iteration
+    let mixed = "{\"5\":6}\r\n\r\nnot json\n{\"7\":8}";
+    let results: Vec<_> = json_map_lines::<i32, i32>(mixed).collect();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap()[&5], 6);
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap()[&7], 8);
+  }
+
+
+  // Mimics a fixed-capacity collection such as arrayvec::ArrayVec, which panics when overfilled
+  struct FixedCapacity<T, const N: usize>(Vec<T>);
+
+  impl<T, const N: usize> FromIterator<T> for FixedCapacity<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+      let items: Vec<T> = iter.into_iter().collect();
+      assert!(items.len() <= N, "capacity exceeded");
+      FixedCapacity(items)
+    }
+  }
+
+  #[test]
+  fn test_struct_json_to_collection() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+
+    let deser: HashMap<Test, Test> = json_to_collection(&serialized).unwrap();
+    assert_eq!(data, deser);
+    let mut pairs: Vec<(Test, Test)> = json_to_collection(&serialized).unwrap();
+    pairs.sort_by_key(|(k, _)| k.a);
+    assert_eq!(pairs, vec![(Test {a: 3, b: 5}, Test {a: 7, b: 9}), (Test {a: 11, b: 12}, Test {a: 13, b: 14})]);
+
+    let fixed: FixedCapacity<(Test, Test), 2> = json_to_collection_bounded(&serialized, 2).unwrap();
+    assert_eq!(fixed.0.len(), 2);
+    let err = json_to_collection_bounded::<FixedCapacity<(Test, Test), 1>, _, _>(&serialized, 1).err().unwrap();
+    assert_eq!(err.to_string(), "JSON map has 2 entries, which exceeds the capacity of 1");
+
+    let err = json_to_collection::<Vec<(Test, Test)>, Test, Test>("{\"x\":{\"a\":1,\"b\":2}}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"x\""));
+  }
+
+
+  #[test]
+  fn test_struct_json_to_map_retain() {
+    let mut data = HashMap::<Test, Test>::new();
+    for i in 0..10 {
+      data.insert(Test {a: i, b: i * 2}, Test {a: i, b: 0});
+    }
+    let serialized = data.to_json_map().unwrap();
+    let deser: HashMap<Test, Test> = json_to_map_retain(&serialized, |k: &Test, _v: &Test| k.a % 2 == 0).unwrap();
+
+    let mut expected = data.clone();
+    expected.retain(|k, _v| k.a % 2 == 0);
+    assert_eq!(deser, expected);
+
+    // discarded entries are still validated
+    let err = json_to_map_retain::<i32, i32, _>("{\"5\":6,\"7\":\"x\"}", |_k, _v| false).unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key \"7\""));
+  }
+
+
+  #[test]
+  fn test_number_keys() {
+    let mut data = HashMap::<serde_json::Number, i32>::new();
+    data.insert(serde_json::Number::from(u64::MAX), 1);
+    data.insert(serde_json::Number::from(i64::MIN), 2);
+    data.insert(serde_json::Number::from_f64(0.1).unwrap(), 3);
+    data.insert(serde_json::Number::from_f64(-1.5e300).unwrap(), 4);
+    let serialized = data.to_json_map().unwrap();
+    let raw: HashMap<String, i32> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw[&u64::MAX.to_string()], 1);
+    assert_eq!(raw[&i64::MIN.to_string()], 2);
+
+    let deser: HashMap<serde_json::Number, i32> = json_to_map(&serialized).unwrap();
+    assert_eq!(data, deser);
+    let deser: Vec<(serde_json::Number, i32)> = json_to_vec(&serialized).unwrap();
+    assert_eq!(deser.len(), 4);
+    assert!(deser.iter().all(|(k, v)| data[k] == *v));
+  }
+
+  #[cfg(feature = "arbitrary_precision")]
+  #[test]
+  fn test_number_keys_arbitrary_precision() {
+    let ser = "{\"3.14159265358979323846264338327950288\":1,\"123456789012345678901234567890\":2}";
+    let deser: HashMap<serde_json::Number, i32> = json_to_map(ser).unwrap();
+    let keys: Vec<String> = deser.keys().map(|k| k.to_string()).collect();
+    assert!(keys.contains(&"3.14159265358979323846264338327950288".to_string()));
+    assert!(keys.contains(&"123456789012345678901234567890".to_string()));
+
+    let reserialized = deser.to_json_map().unwrap();
+    let again: HashMap<serde_json::Number, i32> = json_to_map(&reserialized).unwrap();
+    assert_eq!(deser, again);
+  }
+
+
+  #[test]
+  fn test_struct_slices_to_json_map() {
+    let keys = vec![Test {a: 3, b: 5}, Test {a: 11, b: 12}];
+    let values = vec![Test {a: 7, b: 9}, Test {a: 13, b: 14}];
+    let serialized = slices_to_json_map(&keys, &values).unwrap();
+
+    let zipped: Vec<(Test, Test)> = keys.iter().copied().zip(values.iter().copied()).collect();
+    assert_eq!(serialized, zipped.to_json_map().unwrap());
+
+    let err = slices_to_json_map(&keys, &values[..1]).unwrap_err();
+    assert_eq!(err.to_string(), "slice lengths differ: 2 keys and 1 values");
+    assert_eq!(slices_to_json_map::<i32, i32>(&[], &[]).unwrap(), "{}");
+  }
+
+
+  #[test]
+  fn test_struct_json_to_slices() {
+    let keys = vec![Test {a: 11, b: 12}, Test {a: 3, b: 5}];
+    let values = vec![Test {a: 13, b: 14}, Test {a: 7, b: 9}];
+    let serialized = slices_to_json_map(&keys, &values).unwrap();
+    let (dkeys, dvalues): (Vec<Test>, Vec<Test>) = json_to_slices(&serialized).unwrap();
+    assert_eq!(dkeys, keys);
+    assert_eq!(dvalues, values);
+
+    let err = json_to_slices::<i32, i32>("{\"5\":6} x").unwrap_err();
+    assert!(err.to_string().starts_with("trailing characters"));
+  }
+
+
+  #[test]
+  fn test_string_inputs() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    let serialized: String = data.to_json_map().unwrap();
+
+    assert_eq!(json_to_map::<Test, Test>(serialized.as_str()).unwrap(), data);
+    assert_eq!(json_to_map::<Test, Test>(&serialized).unwrap(), data);
+    assert_eq!(json_to_map::<Test, Test>(std::borrow::Cow::Borrowed(serialized.as_str())).unwrap(), data);
+    let vec: Vec<(Test, Test)> = json_to_vec(std::borrow::Cow::<str>::Owned(serialized.clone())).unwrap();
+    assert_eq!(vec, vec![(Test {a: 3, b: 5}, Test {a: 7, b: 9})]);
+    assert_eq!(json_to_map::<Test, Test>(serialized).unwrap(), data);
+  }
+
+
+  // A minimal stand-in for erased_serde: trait objects which serialize through a dynamically dispatched method
+  trait Plugin {
+    fn state(&self) -> serde_json::Value;
+  }
+
+  impl Serialize for dyn Plugin {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      self.state().serialize(serializer)
+    }
+  }
+
+  impl Plugin for Test {
+    fn state(&self) -> serde_json::Value {
+      serde_json::to_value(self).unwrap()
+    }
+  }
+
+  impl Plugin for String {
+    fn state(&self) -> serde_json::Value {
+      serde_json::Value::String(self.clone())
+    }
+  }
+
+  #[test]
+  fn test_trait_object_values() {
+    let mut data = HashMap::<Test, Box<dyn Plugin>>::new();
+    data.insert(Test {a: 3, b: 5}, Box::new(Test {a: 7, b: 9}));
+    let serialized = data.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"{\\\"a\\\":3,\\\"b\\\":5}\":{\"a\":7,\"b\":9}}");
+
+    data.insert(Test {a: 11, b: 12}, Box::new("foo".to_string()));
+    let serialized = data.to_json_map().unwrap();
+    let deser: HashMap<Test, serde_json::Value> = json_to_map(&serialized).unwrap();
+    assert_eq!(deser[&Test {a: 11, b: 12}], serde_json::Value::String("foo".to_string()));
+
+    let mut writer = JsonMapWriter::new(Vec::new());
+    let plugin: &dyn Plugin = &Test {a: 7, b: 9};
+    writer.write_entry(&5, plugin).unwrap();
+    assert_eq!(String::from_utf8(writer.finish().unwrap()).unwrap(), "{\"5\":{\"a\":7,\"b\":9}}");
+  }
+
+
+  #[test]
+  fn test_streaming_error_entry_index() {
+    let ser = "{\"1\":1,\"2\":2,\"3\":3,\"4\":\"x\",\"5\":5}";
+    let err = json_to_vec_all::<i32, i32>(ser).unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key \"4\": invalid type: string \"x\", expected i32"), "{}", err);
+    assert!(err.contains(" at entry 3 "), "{}", err);
+
+    let ser = "{\"1\":1,\"x\":2}";
+    let err = json_to_vec_all::<i32, i32>(ser).unwrap_err().to_string();
+    assert!(err.starts_with("invalid key \"x\": "), "{}", err);
+    assert!(err.contains(" at entry 1 "), "{}", err);
+  }
+
+
+  #[test]
+  fn test_uniform_keys() {
+    let mut strings = HashMap::<String, i32>::new();
+    strings.insert("foo".to_string(), 5);
+    let serialized = strings.to_json_map_uniform().unwrap();
+    assert_eq!(serialized, "{\"\\\"foo\\\"\":5}");
+    assert_eq!(json_to_map_uniform::<String, i32>(&serialized).unwrap(), strings);
+
+    let err = json_to_map_uniform::<String, i32>("{\"foo\":5}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"foo\": "), "{}", err);
+
+    // keys which are not string-like are encoded identically to to_json_map()
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    assert_eq!(data.to_json_map_uniform().unwrap(), data.to_json_map().unwrap());
+    assert_eq!(json_to_map_uniform::<Test, Test>(&data.to_json_map().unwrap()).unwrap(), data);
+
+    let nan = vec![(f64::NAN, 1)];
+    assert_eq!(nan.iter().map(|(k, v)| (k, v)).to_json_map_uniform().unwrap_err().to_string(), "float key must be finite");
+  }
+
+  #[test]
+  fn test_to_json_map_cow() {
+    let empty = HashMap::<Test, Test>::new();
+    assert!(matches!(empty.to_json_map_cow().unwrap(), std::borrow::Cow::Borrowed("{}")));
+
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 4, b: 6}, Test {a: 8, b: 10});
+    assert_eq!(data.to_json_map_cow().unwrap(), data.to_json_map().unwrap());
+    let nan = vec![(f64::NAN, 1)];
+    assert!(nan.iter().map(|(k, v)| (k, v)).to_json_map_cow().is_err());
+
+    // String keys are written by serde_json directly, including those which need escaping
+    let mut strings = std::collections::BTreeMap::<String, i32>::new();
+    strings.insert("foo".to_string(), 5);
+    strings.insert("a\"b\n".to_string(), 6);
+    let serialized = strings.to_json_map_cow().unwrap();
+    assert_eq!(serialized, serde_json::to_string(&strings).unwrap());
+    assert_eq!(serialized, strings.to_json_map().unwrap());
+    assert_eq!(json_to_btreemap::<String, i32>(&serialized).unwrap(), strings);
+  }
+
+
+  #[test]
+  fn test_net_addr_keys() {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    let mut v4 = HashMap::<Ipv4Addr, i32>::new();
+    v4.insert(Ipv4Addr::new(127, 0, 0, 1), 5);
+    let serialized = v4.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"127.0.0.1\":5}");
+    assert_eq!(json_to_map::<Ipv4Addr, i32>(&serialized).unwrap(), v4);
+
+    let mut v6 = HashMap::<IpAddr, i32>::new();
+    v6.insert(IpAddr::V6(Ipv6Addr::LOCALHOST), 6);
+    let serialized = v6.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"::1\":6}");
+    assert_eq!(json_to_map::<IpAddr, i32>(&serialized).unwrap(), v6);
+
+    let mut sockets = Vec::<(SocketAddr, i32)>::new();
+    sockets.push(("10.0.0.1:8080".parse().unwrap(), 1));
+    sockets.push(("[2001:db8::1]:443".parse().unwrap(), 2));
+    let serialized = sockets.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"10.0.0.1:8080\":1,\"[2001:db8::1]:443\":2}");
+    assert_eq!(json_to_vec_all::<SocketAddr, i32>(&serialized).unwrap(), sockets);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Connections {
+      #[serde(with = "any_key_map")]
+      open: HashMap<SocketAddr, i32>
+    }
+    let conns = Connections { open: sockets.iter().copied().collect() };
+    let ser = serde_json::to_string(&conns).unwrap();
+    assert_eq!(serde_json::from_str::<Connections>(&ser).unwrap(), conns);
+
+    // quoted keys written by earlier versions are still accepted
+    assert_eq!(json_to_map::<Ipv4Addr, i32>("{\"\\\"127.0.0.1\\\"\":5}").unwrap(), v4);
+    let err = json_to_map::<Ipv4Addr, i32>("{\"localhost\":5}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"localhost\": "), "{}", err);
+  }
+
+
+  #[test]
+  fn test_struct_drain_into_json_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let expected = data.clone();
+    let serialized = data.drain().into_json_map().unwrap();
+    assert!(data.is_empty());
+    assert_eq!(json_to_map::<Test, Test>(&serialized).unwrap(), expected);
+
+    // the map can be refilled and drained again
+    data.insert(Test {a: 1, b: 2}, Test {a: 3, b: 4});
+    assert_eq!(data.drain().into_json_map().unwrap(), "{\"{\\\"a\\\":1,\\\"b\\\":2}\":{\"a\":3,\"b\":4}}");
+
+    let mut vec = vec![(Test {a: 3, b: 5}, Test {a: 7, b: 9}), (Test {a: 11, b: 12}, Test {a: 13, b: 14})];
+    let serialized = vec.drain(1..).into_json_map().unwrap();
+    assert_eq!(vec.len(), 1);
+    assert_eq!(json_to_vec::<Test, Test>(&serialized).unwrap(), vec![(Test {a: 11, b: 12}, Test {a: 13, b: 14})]);
+  }
+
+
+  #[test]
+  fn test_sorted_native() {
+    let vec = vec![(10, 3), (1, 1), (2, 2), (1, 0)];
+    assert_eq!(vec.to_json_map_sorted_native().unwrap(), "{\"1\":1,\"1\":0,\"2\":2,\"10\":3}");
+    assert_eq!(vec.clone().into_json_map_sorted_native().unwrap(), "{\"1\":1,\"1\":0,\"2\":2,\"10\":3}");
+
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    let serialized = data.clone().into_json_map_sorted_native().unwrap();
+    let sorted: Vec<(Test, Test)> = vec![(Test {a: 3, b: 5}, Test {a: 7, b: 9}), (Test {a: 11, b: 12}, Test {a: 13, b: 14})];
+    assert_eq!(serialized, sorted.to_json_map().unwrap());
+    assert_eq!(json_to_vec_all::<Test, Test>(&serialized).unwrap(), sorted);
+  }
+
+
+  #[test]
+  fn test_struct_json_to_sorted_vec() {
+    let mut data = HashMap::<Test, Test>::new();
+    for i in (0..20).rev() {
+      data.insert(Test {a: i, b: -i}, Test {a: i, b: 0});
+    }
+    let serialized = data.to_json_map().unwrap();
+    let table: Vec<(Test, Test)> = json_to_sorted_vec(&serialized).unwrap();
+    assert_eq!(table.len(), 20);
+    assert!(table.windows(2).all(|w| w[0].0 < w[1].0));
+    for (k, v) in data.iter() {
+      let idx = table.binary_search_by(|(tk, _)| tk.cmp(k)).unwrap();
+      assert_eq!(table[idx].1, *v);
+    }
+  }
+
+
+  #[test]
+  fn test_recursion_limit() {
+    // the document itself is too deep
+    let deep_value = format!("{{\"5\":{}{}}}", "[".repeat(10), "]".repeat(10));
+    assert!(json_to_map_with_recursion_limit::<i32, serde_json::Value>(&deep_value, 11).is_ok());
+    let err = json_to_map_with_recursion_limit::<i32, serde_json::Value>(&deep_value, 10).unwrap_err();
+    assert_eq!(err.to_string(), "recursion limit exceeded");
+
+    // a key nested beyond serde_json's own limit is an error rather than a stack overflow
+    let deep_key = format!("{}{}", "[".repeat(1000), "]".repeat(1000));
+    let ser = serde_json::to_string(&HashMap::from([(deep_key, 1)])).unwrap();
+    let err = json_to_map::<serde_json::Value, i32>(&ser).unwrap_err();
+    assert!(err.to_string().contains("recursion limit exceeded"), "{}", err);
+    let err = json_to_map_with_recursion_limit::<serde_json::Value, i32>(&ser, 64).unwrap_err();
+    assert!(err.to_string().ends_with(": recursion limit exceeded"), "{}", err);
+
+    // brackets inside strings are not counted
+    let ser = "{\"\\\"[[[[\\\"\":1}";
+    assert_eq!(json_to_map_with_recursion_limit::<serde_json::Value, i32>(ser, 1).unwrap().len(), 1);
+    // String keys are plain text, so their brackets are not nesting
+    assert_eq!(json_to_map_with_recursion_limit::<String, i32>("{\"[[[[\":1}", 1).unwrap()["[[[["], 1);
+  }
+
+
+  #[derive(Debug)]
+  struct ProducerError(i32);
+
+  impl std::fmt::Display for ProducerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "failed to produce entry {}", self.0)
+    }
+  }
+
+  impl std::error::Error for ProducerError {}
+
+  #[test]
+  fn test_struct_try_iter_to_json_map() {
+    let entries: Vec<Result<(Test, Test), ProducerError>> = vec![Ok((Test {a: 3, b: 5}, Test {a: 7, b: 9}))];
+    let serialized = try_iter_to_json_map(entries).unwrap();
+    assert_eq!(serialized, vec![(Test {a: 3, b: 5}, Test {a: 7, b: 9})].to_json_map().unwrap());
+
+    let mut produced = 0;
+    let entries = (0..10).map(|i| {
+      produced += 1;
+      if i == 2 { Err(ProducerError(i)) } else { Ok((Test {a: i, b: i}, i)) }
+    });
+    let err = try_iter_to_json_map(entries).unwrap_err();
+    assert_eq!(err.to_string(), "failed to produce entry 2");
+    assert_eq!(produced, 3);
+  }
+
+
+  #[test]
+  fn test_struct_framed() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let empty = HashMap::<Test, Test>::new();
+
+    let mut stream = Vec::new();
+    data.to_json_map_framed(&mut stream).unwrap();
+    empty.to_json_map_framed(&mut stream).unwrap();
+    let len = u32::from_le_bytes([stream[0], stream[1], stream[2], stream[3]]) as usize;
+    assert_eq!(&stream[4..4 + len], data.to_json_map().unwrap().as_bytes());
+
+    let mut reader = std::io::Cursor::new(stream);
+    assert_eq!(json_to_map_framed::<Test, Test, _>(&mut reader).unwrap(), data);
+    assert_eq!(json_to_map_framed::<Test, Test, _>(&mut reader).unwrap(), empty);
+    // the stream is exhausted
+    assert!(json_to_map_framed::<Test, Test, _>(&mut reader).unwrap_err().is_io());
+
+    // a truncated frame is an io error
+    let mut stream = Vec::new();
+    data.to_json_map_framed(&mut stream).unwrap();
+    stream.pop();
+    assert!(json_to_map_framed::<Test, Test, _>(stream.as_slice()).unwrap_err().is_io());
+
+    // an oversized length prefix over a short body is an io error, without allocating the claimed length
+    let mut stream = u32::MAX.to_le_bytes().to_vec();
+    stream.extend_from_slice(b"{}");
+    let err = json_to_map_framed::<Test, Test, _>(stream.as_slice()).unwrap_err();
+    assert!(err.is_io());
+    assert_eq!(err.to_string(), "frame is shorter than its length prefix");
+  }
+
+
+  // Intentionally has no Deserialize impl
+  #[derive(Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+  struct AuditKey {
+    user: u32,
+    action: &'static str
+  }
+
+  #[test]
+  fn test_serialize_only_keys() {
+    let mut data = HashMap::<AuditKey, i32>::new();
+    data.insert(AuditKey {user: 1, action: "login"}, 5);
+    let expected = "{\"{\\\"user\\\":1,\\\"action\\\":\\\"login\\\"}\":5}";
+    assert_eq!(data.to_json_map().unwrap(), expected);
+    assert_eq!(data.iter().to_json_map_filtered(|_k, _v| true).unwrap(), expected);
+    assert_eq!(data.json_map_len().unwrap(), expected.len());
+
+    let mut writer = JsonMapWriter::new(Vec::new());
+    writer.write_entry(&AuditKey {user: 1, action: "login"}, &5).unwrap();
+    assert_eq!(writer.finish().unwrap(), expected.as_bytes());
+
+    #[derive(Serialize)]
+    struct AuditLog {
+      #[serde(serialize_with = "any_key_map::serialize")]
+      counts: HashMap<AuditKey, i32>,
+      #[serde(serialize_with = "any_key_vec::serialize")]
+      entries: Vec<(AuditKey, i32)>
+    }
+    let log = AuditLog {counts: HashMap::new(), entries: vec![(AuditKey {user: 2, action: "logout"}, 1)]};
+    assert_eq!(serde_json::to_string(&log).unwrap(), "{\"counts\":{},\"entries\":{\"{\\\"user\\\":2,\\\"action\\\":\\\"logout\\\"}\":1}}");
+
+    assert_eq!(data.into_json_map_sorted_native().unwrap(), expected);
+  }
+
+
+  // A key whose custom Serialize impl produces an empty string
+  #[derive(PartialEq, Eq, Hash)]
+  struct Blank(i32);
+
+  impl Serialize for Blank {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      serializer.serialize_str("")
+    }
+  }
+
+  #[test]
+  fn test_strict_empty_keys() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    assert_eq!(data.to_json_map_strict().unwrap(), data.to_json_map().unwrap());
+
+    let mut paths = HashMap::<std::path::PathBuf, i32>::new();
+    paths.insert(std::path::PathBuf::new(), 1);
+    assert_eq!(paths.to_json_map_strict().unwrap_err().to_string(), "key is an empty string");
+
+    // an empty JSON string is not empty once quoted
+    let mut blanks = HashMap::<Blank, i32>::new();
+    blanks.insert(Blank(1), 1);
+    blanks.insert(Blank(2), 2);
+    assert_eq!(blanks.to_json_map_strict().unwrap(), blanks.to_json_map().unwrap());
+    assert!(blanks.to_json_map_strict().unwrap().contains("\"\\\"\\\"\""));
+  }
+
+
+  // Stands in for timestamp types such as chrono::DateTime, which serialize as RFC 3339 strings
+  #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+  struct Timestamp {
+    day: u32,
+    second: u32
+  }
+
+  impl Serialize for Timestamp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      serializer.collect_str(&format_args!("2024-01-{:02}T00:00:{:02}Z", self.day, self.second))
+    }
+  }
+
+  impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      let s = String::deserialize(deserializer)?;
+      let parse = |range: std::ops::Range<usize>| s.get(range).and_then(|p| p.parse().ok());
+      match (parse(8..10), parse(17..19)) {
+        (Some(day), Some(second)) => Ok(Timestamp {day, second}),
+        _ => Err(serde::de::Error::custom("invalid timestamp"))
+      }
+    }
+  }
+
+  #[test]
+  fn test_timestamp_keys() {
+    let mut data = HashMap::<Timestamp, i32>::new();
+    data.insert(Timestamp {day: 1, second: 5}, 1);
+    data.insert(Timestamp {day: 31, second: 59}, 2);
+    let serialized = data.to_json_map().unwrap();
+    let raw: HashMap<String, i32> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["\"2024-01-01T00:00:05Z\""], 1);
+
+    assert_eq!(json_to_map::<Timestamp, i32>(&serialized).unwrap(), data);
+    let mut all = json_to_vec_all::<Timestamp, i32>(&serialized).unwrap();
+    all.sort_by_key(|(_, v)| *v);
+    assert_eq!(all, vec![(Timestamp {day: 1, second: 5}, 1), (Timestamp {day: 31, second: 59}, 2)]);
+  }
+
+
+  #[test]
+  fn test_struct_serialize_map_entries() {
+    use serde::ser::SerializeMap;
+
+    struct Combined<'a>(&'a HashMap<Test, Test>, &'a HashMap<String, i32>);
+
+    impl Serialize for Combined<'_> {
+      fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut ser_map = serializer.serialize_map(None)?;
+        serialize_map_entries(&mut ser_map, self.0.iter())?;
+        serialize_map_entries(&mut ser_map, self.1.iter())?;
+        ser_map.end()
+      }
+    }
+
+    let mut structs = HashMap::<Test, Test>::new();
+    structs.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    let mut strings = HashMap::<String, i32>::new();
+    strings.insert("foo".to_string(), 1);
+    let serialized = serde_json::to_string(&Combined(&structs, &strings)).unwrap();
+    assert_eq!(serialized, "{\"{\\\"a\\\":3,\\\"b\\\":5}\":{\"a\":7,\"b\":9},\"foo\":1}");
+
+    let floats = vec![(f64::NAN, 1)];
+    let mut buf = Vec::new();
+    let mut ser = serde_json::Serializer::new(&mut buf);
+    let mut ser_map = serde::Serializer::serialize_map(&mut ser, None).unwrap();
+    let err = serialize_map_entries(&mut ser_map, floats.iter().map(|(k, v)| (k, v))).unwrap_err();
+    assert_eq!(err.to_string(), "float key must be finite");
+  }
+
+
+  #[test]
+  fn test_struct_json_to_map_allowed() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+
+    let allowed: std::collections::HashSet<Test> = data.keys().copied().collect();
+    assert_eq!(json_to_map_allowed::<Test, Test>(&serialized, &allowed).unwrap(), data);
+
+    let partial: std::collections::HashSet<Test> = [Test {a: 3, b: 5}].into_iter().collect();
+    let err = json_to_map_allowed::<Test, Test>(&serialized, &partial).unwrap_err();
+    assert_eq!(err.to_string(), "invalid key \"{\\\"a\\\":11,\\\"b\\\":12}\": key is not allowed");
+
+    let none = std::collections::HashSet::<Test>::new();
+    assert!(json_to_map_allowed::<Test, Test>("{}", &none).unwrap().is_empty());
+  }
+
+
+  #[test]
+  fn test_struct_write_json_map() {
+    let mut data = HashMap::<Test, String>::new();
+    data.insert(Test {a: 3, b: 5}, "日本語 and \"quotes\"".to_string());
+    data.insert(Test {a: 11, b: 12}, "🦀".to_string());
+    let mut out = String::from("prefix ");
+    data.write_json_map(&mut out).unwrap();
+    assert_eq!(out, format!("prefix {}", data.to_json_map().unwrap()));
+
+    // errors from the fmt::Write sink are returned
+    struct Full;
+    impl std::fmt::Write for Full {
+      fn write_str(&mut self, _s: &str) -> std::fmt::Result {
+        Err(std::fmt::Error)
+      }
+    }
+    assert!(data.write_json_map(&mut Full).unwrap_err().is_io());
+  }
+
+
+  #[cfg(feature = "rc")]
+  #[test]
+  fn test_struct_shared_values() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+    let shared = Arc::new(Test {a: 7, b: 9});
+    let mut data = HashMap::<Test, Arc<Test>>::new();
+    data.insert(Test {a: 3, b: 5}, shared.clone());
+    data.insert(Test {a: 11, b: 12}, shared.clone());
+    let serialized = data.to_json_map().unwrap();
+
+    let mut plain = HashMap::<Test, Test>::new();
+    plain.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    plain.insert(Test {a: 11, b: 12}, Test {a: 7, b: 9});
+    assert_eq!(json_to_map::<Test, Test>(&serialized).unwrap(), plain);
+
+    // deserialized values are separate allocations rather than shared
+    let deser: HashMap<Test, Arc<Test>> = json_to_map(&serialized).unwrap();
+    assert_eq!(deser, data);
+    assert!(!Arc::ptr_eq(&deser[&Test {a: 3, b: 5}], &deser[&Test {a: 11, b: 12}]));
+
+    let rcs: Vec<(Test, Rc<Test>)> = vec![(Test {a: 3, b: 5}, Rc::new(Test {a: 7, b: 9}))];
+    let serialized = rcs.to_json_map().unwrap();
+    assert_eq!(json_to_vec::<Test, Rc<Test>>(&serialized).unwrap(), rcs);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Graph {
+      #[serde(with = "any_key_map")]
+      nodes: HashMap<Test, Arc<Test>>
+    }
+    let graph = Graph {nodes: data};
+    let ser = serde_json::to_string(&graph).unwrap();
+    assert_eq!(serde_json::from_str::<Graph>(&ser).unwrap(), graph);
+  }
+
+
+  #[test]
+  fn test_struct_lossy() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let mut doc: serde_json::Value = serde_json::from_str(&data.to_json_map().unwrap()).unwrap();
+    let obj = doc.as_object_mut().unwrap();
+    obj.insert("not a key".to_string(), serde_json::json!({"a": 1, "b": 2}));
+    obj.insert("{\"a\":1,\"b\":2}".to_string(), serde_json::json!("not a value"));
+    let corrupted = doc.to_string();
+
+    let deser: HashMap<Test, Test> = json_to_iter_lossy(&corrupted).unwrap().collect();
+    assert_eq!(deser, data);
+
+    let (entries, skipped) = json_to_vec_lossy::<Test, Test>(&corrupted).unwrap();
+    assert_eq!(entries.into_iter().collect::<HashMap<_, _>>(), data);
+    assert_eq!(skipped.len(), 2);
+    assert_eq!(skipped["{\"a\":1,\"b\":2}"], serde_json::json!("not a value"));
+
+    assert!(json_to_iter_lossy::<Test, Test>("[]").is_err());
+  }
+
+
+  #[test]
+  fn test_struct_sorted_by_key() {
+    let mut data = std::collections::BTreeMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 1}, Test {a: 13, b: 14});
+    let by_b = data.to_json_map_sorted_by_key(|x, y| x.b.cmp(&y.b)).unwrap();
+    let expected: Vec<(Test, Test)> = vec![(Test {a: 11, b: 1}, Test {a: 13, b: 14}), (Test {a: 3, b: 5}, Test {a: 7, b: 9})];
+    assert_eq!(by_b, expected.to_json_map().unwrap());
+    assert_eq!(json_to_vec_all::<Test, Test>(&by_b).unwrap(), expected);
+
+    let vec: Vec<(Test, Test)> = data.clone().into_iter().collect();
+    assert_eq!(vec.to_json_map_sorted_by_key(|x, y| x.b.cmp(&y.b)).unwrap(), by_b);
+    // equal keys keep their order
+    assert_eq!(vec.to_json_map_sorted_by_key(|_, _| std::cmp::Ordering::Equal).unwrap(), vec.to_json_map().unwrap());
+  }
+
+
+  #[test]
+  fn test_struct_try_from_json_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 12}, Test {a: 13, b: 14});
+    let serialized = data.to_json_map().unwrap();
+
+    assert_eq!(HashMap::<Test, Test>::try_from_json_map(&serialized).unwrap(), data);
+    let btree = std::collections::BTreeMap::<Test, Test>::try_from_json_map(&serialized).unwrap();
+    assert_eq!(btree.into_iter().collect::<HashMap<_, _>>(), data);
+    let vec = Vec::<(Test, Test)>::try_from_json_map(&serialized).unwrap();
+    assert_eq!(vec, json_to_vec::<Test, Test>(&serialized).unwrap());
+
+    let err = Vec::<(Test, Test)>::try_from_json_map("{\"x\":1}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"x\""), "{}", err);
+  }
+
+
+  #[test]
+  fn test_struct_unit_values() {
+    let mut set = HashMap::<Test, ()>::new();
+    set.insert(Test {a: 3, b: 5}, ());
+    set.insert(Test {a: 11, b: 12}, ());
+    let serialized = set.to_json_map().unwrap();
+    let raw: HashMap<String, serde_json::Value> = serde_json::from_str(&serialized).unwrap();
+    assert!(raw.values().all(|v| v.is_null()));
+
+    assert_eq!(json_to_map::<Test, ()>(&serialized).unwrap(), set);
+    assert_eq!(json_to_vec_all::<Test, ()>(&serialized).unwrap().len(), 2);
+    assert_eq!(json_to_iter::<Test, ()>(&serialized).unwrap().collect::<Result<HashMap<_, _>, _>>().unwrap(), set);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Visited {
+      #[serde(with = "any_key_map")]
+      cells: HashMap<Test, ()>
+    }
+    let visited = Visited {cells: set};
+    let ser = serde_json::to_string(&visited).unwrap();
+    assert_eq!(serde_json::from_str::<Visited>(&ser).unwrap(), visited);
+
+    let err = json_to_map::<Test, ()>("{\"{\\\"a\\\":1,\\\"b\\\":2}\":1}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key"), "{}", err);
+  }
+
+
+  #[test]
+  fn test_struct_try_into_json_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    let expected = data.to_json_map().unwrap();
+    assert_eq!(data.clone().try_into_json_map().unwrap(), expected);
+    let btree: std::collections::BTreeMap<Test, Test> = data.clone().into_iter().collect();
+    assert_eq!(btree.try_into_json_map().unwrap(), expected);
+
+    let mut floats = std::collections::BTreeMap::<Vec<u8>, f64>::new();
+    floats.insert(vec![1], 1.5);
+    assert_eq!(floats.try_into_json_map().unwrap(), "{\"[1]\":1.5}");
+
+    let bad = vec![(1.0f32, Test {a: 1, b: 2}), (f32::INFINITY, Test {a: 3, b: 4})];
+    let (recovered, err) = bad.clone().try_into_json_map().unwrap_err();
+    assert_eq!(err.to_string(), "float key must be finite");
+    assert_eq!(recovered, bad);
+  }
+
+
+  #[test]
+  fn test_array_keys() {
+    let mut paths = HashMap::<Vec<u8>, i32>::new();
+    paths.insert(vec![1, 2, 3], 1);
+    paths.insert(vec![], 2);
+    let serialized = paths.to_json_map().unwrap();
+    let raw: HashMap<String, i32> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["[1,2,3]"], 1);
+    assert_eq!(raw["[]"], 2);
+    assert_eq!(json_to_map::<Vec<u8>, i32>(&serialized).unwrap(), paths);
+
+    let mut coords = HashMap::<[i16; 3], Test>::new();
+    coords.insert([1, -2, 3], Test {a: 7, b: 9});
+    let serialized = coords.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"[1,-2,3]\":{\"a\":7,\"b\":9}}");
+    assert_eq!(json_to_map::<[i16; 3], Test>(&serialized).unwrap(), coords);
+    assert_eq!(json_to_vec_all::<[i16; 3], Test>(&serialized).unwrap(), vec![([1, -2, 3], Test {a: 7, b: 9})]);
+
+    let err = json_to_map::<[i16; 3], Test>("{\"[1,2]\":{\"a\":7,\"b\":9}}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"[1,2]\""), "{}", err);
+    let err = json_to_map::<Vec<u8>, i32>("{\"[256]\":1}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"[256]\""), "{}", err);
+  }
+
+
+  #[test]
+  fn test_struct_json_map_display() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    let display = data.as_json_map_display();
+    // formatting can be repeated
+    assert_eq!(display.to_string(), data.to_json_map().unwrap());
+    assert_eq!(format!("{} {}", display, display), format!("{0} {0}", data.to_json_map().unwrap()));
+    assert_eq!(format!("{}", data.iter().as_json_map_display()), data.to_json_map().unwrap());
+
+    let mut floats = std::collections::BTreeMap::<Vec<u8>, f64>::new();
+    floats.insert(vec![], 1.0);
+    assert_eq!(floats.as_json_map_display().to_string(), "{\"[]\":1.0}");
+
+    // the entries before a failing key have already been written
+    let bad = vec![(1.0, 1), (f64::NAN, 2)];
+    assert_eq!(bad.iter().map(|(k, v)| (k, v)).as_json_map_display().to_string(), "{\"1.0\":1<serialization error>");
+  }
+
+
+  // Stands in for ordered_float::OrderedFloat, which serializes transparently as the inner float
+  #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+  #[serde(transparent)]
+  struct Ordered(f64);
+
+  impl PartialEq for Ordered {
+    fn eq(&self, other: &Self) -> bool {
+      self.0.to_bits() == other.0.to_bits()
+    }
+  }
+
+  impl Eq for Ordered {}
+
+  impl std::hash::Hash for Ordered {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+      self.0.to_bits().hash(state)
+    }
+  }
+
+  #[test]
+  fn test_ordered_float_keys() {
+    let mut data = HashMap::<Ordered, i32>::new();
+    data.insert(Ordered(1.5), 1);
+    data.insert(Ordered(-0.1), 2);
+    data.insert(Ordered(1e300), 3);
+    let serialized = data.to_json_map().unwrap();
+    let raw: HashMap<String, i32> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["1.5"], 1);
+    assert_eq!(json_to_map::<Ordered, i32>(&serialized).unwrap(), data);
+
+    // NaN is written as null, which cannot be read back as a float
+    let mut nan = HashMap::<Ordered, i32>::new();
+    nan.insert(Ordered(f64::NAN), 1);
+    let serialized = nan.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"null\":1}");
+    let err = json_to_map::<Ordered, i32>(&serialized).unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"null\""), "{}", err);
+  }
+
+
+  #[cfg(feature = "parallel")]
+  #[test]
+  fn test_par_json_to_map() {
+    // large enough to be split across threads
+    let data: HashMap<Test, i32> = (0..5000).map(|i| (Test {a: i, b: -i}, i)).collect();
+    let serialized = data.to_json_map().unwrap();
+    assert_eq!(par_json_to_map::<Test, i32>(&serialized).unwrap(), data);
+    assert_eq!(par_json_to_map::<Test, i32>("{}").unwrap(), HashMap::new());
+    // small maps are deserialized on the calling thread
+    let small: HashMap<Test, i32> = data.iter().take(3).map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(par_json_to_map::<Test, i32>(small.to_json_map().unwrap()).unwrap(), small);
+
+    // the first failing entry in key order is reported, regardless of which thread saw it
+    let mut raw: std::collections::BTreeMap<String, serde_json::Value> = serde_json::from_str(&serialized).unwrap();
+    let last = raw.keys().last().unwrap().clone();
+    raw.insert(last.clone(), serde_json::Value::from("bad"));
+    raw.insert("[0]".to_string(), serde_json::Value::from(0));
+    let err = par_json_to_map::<Test, i32>(serde_json::to_string(&raw).unwrap()).unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"[0]\""), "{}", err);
+  }
+
+
+  #[test]
+  fn test_base64_keys() {
+    let mut data = HashMap::<Vec<u8>, Test>::new();
+    data.insert(vec![], Test {a: 1, b: 2});
+    data.insert(vec![0xde, 0xad, 0xbe, 0xef], Test {a: 3, b: 4});
+    let serialized = data.to_json_map_base64_keys().unwrap();
+    let raw: HashMap<String, Test> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["3q2+7w=="], Test {a: 3, b: 4});
+    assert_eq!(raw[""], Test {a: 1, b: 2});
+    assert_eq!(json_to_map_base64_keys::<Vec<u8>, Test>(&serialized).unwrap(), data);
+
+    let mut arrays = HashMap::<[u8; 2], i32>::new();
+    arrays.insert([1, 2], 5);
+    let serialized = arrays.to_json_map_base64_keys().unwrap();
+    assert_eq!(serialized, "{\"AQI=\":5}");
+    assert_eq!(json_to_map_base64_keys::<[u8; 2], i32>(&serialized).unwrap(), arrays);
+
+    let err = json_to_map_base64_keys::<[u8; 2], i32>("{\"AQID\":5}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"AQID\""), "{}", err);
+
+    let mut not_bytes = HashMap::<Test, i32>::new();
+    not_bytes.insert(Test {a: 1, b: 2}, 5);
+    assert_eq!(not_bytes.to_json_map_base64_keys().unwrap_err().to_string(), "key is not a sequence of bytes");
+  }
+
+
+  #[test]
+  fn test_canonical() {
+    let mut data = HashMap::<Test, serde_json::Value>::new();
+    data.insert(Test {a: 10, b: 0}, serde_json::json!({"z": [1.5, {"y": 1, "x": "é"}], "a": null}));
+    data.insert(Test {a: 9, b: 0}, serde_json::json!(1e300));
+    let mut btree = std::collections::BTreeMap::new();
+    btree.extend(data.iter().map(|(k, v)| (*k, v.clone())));
+
+    let serialized = data.to_json_map_canonical().unwrap();
+    assert_eq!(serialized, "{\"{\\\"a\\\":10,\\\"b\\\":0}\":{\"a\":null,\"z\":[1.5,{\"x\":\"é\",\"y\":1}]},\"{\\\"a\\\":9,\\\"b\\\":0}\":1e+300}");
+    assert_eq!(btree.to_json_map_canonical().unwrap(), serialized);
+    assert_eq!(json_to_map::<Test, serde_json::Value>(&serialized).unwrap(), data);
+
+    // keys are compared by bytes, so "B" sorts before "a"
+    let mut strings = HashMap::<String, i32>::new();
+    strings.insert("a".to_string(), 1);
+    strings.insert("B".to_string(), 2);
+    assert_eq!(strings.to_json_map_canonical().unwrap(), r#"{"B":2,"a":1}"#);
+
+    let dupes = [(true, 1), (true, 2)];
+    let err = dupes.iter().map(|(k, v)| (k, v)).to_json_map_canonical().unwrap_err();
+    assert_eq!(err.to_string(), "duplicate key \"true\"");
+  }
+
+
+  #[test]
+  fn test_json_to_map_at() {
+    let mut data = HashMap::<Test, i32>::new();
+    data.insert(Test {a: 3, b: 5}, 6);
+    let inner: serde_json::Value = serde_json::from_str(&data.to_json_map().unwrap()).unwrap();
+    let envelope = serde_json::json!({"data": inner, "pages": [{"a/b": inner}]}).to_string();
+
+    assert_eq!(json_to_map_at::<Test, i32>(&envelope, "/data").unwrap(), data);
+    assert_eq!(json_to_map_at::<Test, i32>(&envelope, "/pages/0/a~1b").unwrap(), data);
+    assert_eq!(json_to_map_at::<Test, i32>(&data.to_json_map().unwrap(), "").unwrap(), data);
+
+    assert_eq!(json_to_map_at::<Test, i32>(&envelope, "/meta").unwrap_err().to_string(), "no value at pointer \"/meta\"");
+    assert_eq!(json_to_map_at::<Test, i32>(&envelope, "/pages").unwrap_err().to_string(), "Value is not a JSON map");
+    let err = json_to_map_at::<i32, i32>(&envelope, "/data").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key"), "{}", err);
+  }
+
+
+  #[test]
+  fn test_option_keys() {
+    let mut data = HashMap::<Option<u32>, Test>::new();
+    data.insert(None, Test {a: 1, b: 2});
+    data.insert(Some(5), Test {a: 3, b: 4});
+    let serialized = data.to_json_map().unwrap();
+    let raw: HashMap<String, Test> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["null"], Test {a: 1, b: 2});
+    assert_eq!(raw["5"], Test {a: 3, b: 4});
+    assert_eq!(json_to_map::<Option<u32>, Test>(&serialized).unwrap(), data);
+
+    // a Some string is quoted, so it cannot be confused with None
+    let mut strings = HashMap::<Option<String>, i32>::new();
+    strings.insert(None, 1);
+    strings.insert(Some("null".to_string()), 2);
+    let serialized = strings.to_json_map().unwrap();
+    let raw: HashMap<String, i32> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["null"], 1);
+    assert_eq!(raw["\"null\""], 2);
+    assert_eq!(json_to_map::<Option<String>, i32>(&serialized).unwrap(), strings);
+
+    // nested options are not distinguishable: Some(None) is also written as null and read back as None
+    let nested = vec![(Some(None::<u32>), 1)];
+    let serialized = nested.to_json_map().unwrap();
+    assert_eq!(serialized, "{\"null\":1}");
+    assert_eq!(json_to_vec::<Option<Option<u32>>, i32>(&serialized).unwrap(), vec![(None, 1)]);
+  }
+
+
+  #[test]
+  fn test_reload_map_from_json() {
+    let mut data = HashMap::<Test, i32>::new();
+    data.insert(Test {a: 3, b: 5}, 6);
+    data.insert(Test {a: 7, b: 9}, 10);
+    let serialized = data.to_json_map().unwrap();
+
+    let mut target = HashMap::<Test, i32>::with_capacity(64);
+    target.insert(Test {a: 1, b: 1}, 1);
+    let capacity = target.capacity();
+    reload_map_from_json(&mut target, &serialized).unwrap();
+    assert_eq!(target, data);
+    assert_eq!(target.capacity(), capacity);
+
+    // a syntax error and an invalid entry both leave the map empty
+    reload_map_from_json(&mut target, "{").unwrap_err();
+    assert!(target.is_empty());
+    target.clone_from(&data);
+    let err = reload_map_from_json(&mut target, "{\"{\\\"a\\\":1,\\\"b\\\":2}\":1,\"5\":2}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"5\""), "{}", err);
+    assert!(target.is_empty());
+  }
+
+
+  #[test]
+  fn test_json_to_map_with_type_names() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    let serialized = data.to_json_map().unwrap();
+    assert_eq!(json_to_map_with_type_names::<Test, Test>(&serialized).unwrap(), data);
+
+    let err = json_to_map_with_type_names::<(i32, i32), Test>(&serialized).unwrap_err().to_string();
+    assert!(err.starts_with("invalid key \"{\\\"a\\\":3,\\\"b\\\":5}\": "), "{}", err);
+    assert!(err.ends_with("(key type is `(i32, i32)`)"), "{}", err);
+
+    let err = json_to_map_with_type_names::<Test, String>(&serialized).unwrap_err().to_string();
+    assert!(err.starts_with("invalid value for key \"{\\\"a\\\":3,\\\"b\\\":5}\": "), "{}", err);
+    assert!(err.contains("(value type is `") && err.ends_with("String`)"), "{}", err);
+  }
+
+
+  #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+  #[serde(tag = "type")]
+  enum Event {
+    Created { id: u64, at: Test },
+    Renamed { name: String, score: f64 },
+    Deleted
+  }
+
+  #[test]
+  fn test_internally_tagged_values() {
+    let mut data = HashMap::<Test, Event>::new();
+    data.insert(Test {a: 1, b: 0}, Event::Created { id: u64::MAX, at: Test {a: 3, b: 5} });
+    data.insert(Test {a: 2, b: 0}, Event::Renamed { name: "foo".to_string(), score: 1.5 });
+    data.insert(Test {a: 3, b: 0}, Event::Deleted);
+    let serialized = data.to_json_map().unwrap();
+    let raw: HashMap<String, serde_json::Value> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["{\"a\":3,\"b\":0}"], serde_json::json!({"type": "Deleted"}));
+
+    assert_eq!(json_to_map::<Test, Event>(&serialized).unwrap(), data);
+    assert_eq!(json_to_iter::<Test, Event>(&serialized).unwrap().collect::<Result<HashMap<_, _>, _>>().unwrap(), data);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Events {
+      #[serde(with = "any_key_map")]
+      map: HashMap<Test, Event>
+    }
+    let events = Events { map: data };
+    let deser: Events = serde_json::from_str(&serde_json::to_string(&events).unwrap()).unwrap();
+    assert_eq!(deser, events);
+
+    let err = json_to_map::<Test, Event>("{\"{\\\"a\\\":1,\\\"b\\\":0}\":{\"type\":\"Moved\"}}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key \"{\\\"a\\\":1,\\\"b\\\":0}\": unknown variant `Moved`"), "{}", err);
+  }
+
+
+  #[test]
+  fn test_json_to_map_indexed() {
+    let data = vec![(Test {a: 9, b: 0}, 1), (Test {a: 1, b: 0}, 2), (Test {a: 5, b: 0}, 3)];
+    let serialized = data.to_json_map().unwrap();
+    let (map, keys) = json_to_map_indexed::<Test, i32>(&serialized).unwrap();
+    assert_eq!(keys, vec![Test {a: 9, b: 0}, Test {a: 1, b: 0}, Test {a: 5, b: 0}]);
+    assert_eq!(map, data.iter().cloned().collect::<HashMap<_, _>>());
+
+    let (map, keys) = json_to_map_indexed::<i32, i32>("{\"2\":1,\"1\":2,\"2\":3}").unwrap();
+    assert_eq!(keys, vec![2, 1]);
+    assert_eq!(map[&2], 3);
+
+    let err = json_to_map_indexed::<i32, i32>("{\"2\":1,\"x\":2}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"x\""), "{}", err);
+  }
+
+
+  #[test]
+  fn test_string_keyed_map_values() {
+    let mut inner = HashMap::<String, Test>::new();
+    inner.insert("foo".to_string(), Test {a: 7, b: 9});
+    let mut data = HashMap::<Test, HashMap<String, Test>>::new();
+    data.insert(Test {a: 3, b: 5}, inner);
+    data.insert(Test {a: 4, b: 6}, HashMap::new());
+
+    // the inner maps are written as plain nested objects by serde_json
+    let serialized = data.to_json_map().unwrap();
+    let raw: HashMap<String, serde_json::Value> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["{\"a\":3,\"b\":5}"], serde_json::json!({"foo": {"a": 7, "b": 9}}));
+    assert_eq!(raw["{\"a\":4,\"b\":6}"], serde_json::json!({}));
+
+    assert_eq!(json_to_map::<Test, HashMap<String, Test>>(&serialized).unwrap(), data);
+    assert_eq!(data.clone().into_json_map().unwrap().len(), serialized.len());
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Mixed {
+      #[serde(with = "any_key_map")]
+      map: HashMap<Test, HashMap<String, Test>>
+    }
+    let mixed = Mixed { map: data };
+    let ser = serde_json::to_string(&mixed).unwrap();
+    assert_eq!(serde_json::from_str::<Mixed>(&ser).unwrap(), mixed);
+  }
+
+
+  // Generates a JSON map with `len` entries on the fly, and fails once `fail_after` bytes have been read
+  struct GeneratedMap {
+    pending: Vec<u8>,
+    next: i32,
+    len: i32,
+    read: usize,
+    fail_after: usize
+  }
+
+  impl std::io::Read for GeneratedMap {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      if self.read >= self.fail_after {
+        return Err(std::io::Error::other("connection reset"));
+      }
+      if self.pending.is_empty() {
+        self.pending = match self.next {
+          0 => b"{".to_vec(),
+          n if n > self.len => vec![],
+          n if n == self.len => b"}".to_vec(),
+          n => format!("{}\"{{\\\"a\\\":{},\\\"b\\\":0}}\":{}", if n > 1 { "," } else { "" }, n, n).into_bytes()
+        };
+        self.next += 1;
+      }
+      let n = std::cmp::min(buf.len(), self.pending.len());
+      buf[..n].copy_from_slice(&self.pending[..n]);
+      self.pending.drain(..n);
+      self.read += n;
+      Ok(n)
+    }
+  }
+
+  #[test]
+  fn test_json_to_iter_streaming() {
+    let map = GeneratedMap { pending: vec![], next: 0, len: 100_000, read: 0, fail_after: usize::MAX };
+    let mut count = 0;
+    for entry in json_to_iter_streaming::<_, Test, i32>(map) {
+      let (key, val) = entry.unwrap();
+      count += 1;
+      assert_eq!(key, Test {a: val, b: 0});
+    }
+    assert_eq!(count, 99_999);
+
+    // entries are parsed lazily, so the ones before a read error are still returned
+    let map = GeneratedMap { pending: vec![], next: 0, len: 100_000, read: 0, fail_after: 100_000 };
+    let mut iter = json_to_iter_streaming::<_, Test, i32>(map);
+    assert_eq!(iter.next().unwrap().unwrap(), (Test {a: 1, b: 0}, 1));
+    let err = iter.find_map(|entry| entry.err()).unwrap();
+    assert!(err.to_string().contains("connection reset"), "{}", err);
+    assert!(iter.next().is_none());
+  }
+
+
+  #[test]
+  fn test_float_keys_fixed_precision() {
+    let data = vec![(0.1 + 0.2, Test {a: 1, b: 2}), (-1.0 / 3.0, Test {a: 3, b: 4}), (1e6, Test {a: 5, b: 6})];
+    let serialized = data.to_json_map_float_keys(3).unwrap();
+    assert_eq!(serialized, "{\"0.300\":{\"a\":1,\"b\":2},\"-0.333\":{\"a\":3,\"b\":4},\"1000000.000\":{\"a\":5,\"b\":6}}");
+
+    // keys are rounded, so they are only equal to the originals within the precision
+    let deser = json_to_vec_float_keys::<f64, Test>(&serialized).unwrap();
+    assert_eq!(deser.len(), 3);
+    for (key, val) in &deser {
+      let original = data.iter().find(|(_, v)| v == val).unwrap();
+      assert!((key - original.0).abs() < 0.0005, "{} {}", key, original.0);
+    }
+    let deser = json_to_vec_float_keys::<f32, Test>(&serialized).unwrap();
+    assert!(deser.contains(&(0.3, Test {a: 1, b: 2})));
+
+    let zero = vec![(0.0f32, 1)];
+    assert_eq!(zero.to_json_map_float_keys(0).unwrap(), "{\"0\":1}");
+
+    let nan = vec![(f64::NAN, 1)];
+    assert_eq!(nan.to_json_map_float_keys(2).unwrap_err().to_string(), "float key must be finite");
+    let strings = vec![("1.5".to_string(), 1)];
+    assert_eq!(strings.to_json_map_float_keys(2).unwrap_err().to_string(), "key is not a number");
+    let err = json_to_vec_float_keys::<f64, i32>("{\"inf\":1}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"inf\": float key must be finite"), "{}", err);
+    let err = json_to_vec_float_keys::<f64, i32>("{\"1,5\":1}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"1,5\""), "{}", err);
+  }
+
+
+  #[test]
+  fn test_merge_json_maps() {
+    let mut shard1 = HashMap::<Test, Vec<i32>>::new();
+    shard1.insert(Test {a: 1, b: 1}, vec![1]);
+    shard1.insert(Test {a: 2, b: 2}, vec![2]);
+    let mut shard2 = HashMap::<Test, Vec<i32>>::new();
+    shard2.insert(Test {a: 2, b: 2}, vec![3]);
+    shard2.insert(Test {a: 3, b: 3}, vec![4]);
+    let inputs = [shard1.to_json_map().unwrap(), shard2.to_json_map().unwrap()];
+    let inputs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+
+    let mut conflicts = vec![];
+    let merged = merge_json_maps::<Test, Vec<i32>, _>(&inputs, |k, mut old, new| {
+      conflicts.push(*k);
+      old.extend(new);
+      old
+    }).unwrap();
+    assert_eq!(conflicts, vec![Test {a: 2, b: 2}]);
+    assert_eq!(merged.len(), 3);
+    assert_eq!(merged[&Test {a: 2, b: 2}], vec![2, 3]);
+    assert_eq!(merge_json_maps::<Test, Vec<i32>, _>(&[], |_k, _old, new| new).unwrap(), HashMap::new());
+
+    let err = merge_json_maps::<Test, Vec<i32>, _>(&[inputs[0], "{\"x\":[]}"], |_k, _old, new| new).unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"x\""), "{}", err);
+    assert!(err.to_string().ends_with(" in input 1"), "{}", err);
+  }
+
+
+  #[test]
+  fn test_lazy_any_key_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 4, b: 6}, Test {a: 8, b: 10});
+    let serialized = data.to_json_map().unwrap();
+
+    let lazy: LazyAnyKeyMap<Test, Test> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(lazy.len(), 2);
+    assert_eq!(lazy.get(&Test {a: 3, b: 5}).unwrap().unwrap(), Test {a: 7, b: 9});
+    assert!(lazy.get(&Test {a: 1, b: 1}).is_none());
+    assert!(lazy.contains_key(&Test {a: 4, b: 6}).unwrap());
+    let keys: HashMap<Test, ()> = lazy.keys().map(|k| (k.unwrap(), ())).collect();
+    assert_eq!(keys.len(), 2);
+
+    // only the requested value is deserialized, so other invalid entries are not reported
+    let lazy: LazyAnyKeyMap<Test, Test> = serde_json::from_str("{\"{\\\"a\\\":1,\\\"b\\\":2}\":{\"a\":1,\"b\":2},\"{\\\"a\\\":3,\\\"b\\\":4}\":5,\"x\":1}").unwrap();
+    assert_eq!(lazy.get(&Test {a: 1, b: 2}).unwrap().unwrap(), Test {a: 1, b: 2});
+    let err = lazy.get(&Test {a: 3, b: 4}).unwrap().unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key \"{\\\"a\\\":3,\\\"b\\\":4}\""), "{}", err);
+    assert!(lazy.keys().any(|k| k.is_err()));
+
+    let strings: LazyAnyKeyMap<String, i32> = LazyAnyKeyMap::new(serde_json::from_str("{\"foo\":5}").unwrap());
+    assert_eq!(strings.get(&"foo".to_string()).unwrap().unwrap(), 5);
+    assert!(serde_json::from_str::<LazyAnyKeyMap<String, i32>>("[]").is_err());
+  }
+
+
+  #[test]
+  fn test_map_sorted_native_matches_btreemap() {
+    let data: HashMap<Test, i32> = (0..100).map(|i| (Test {a: i % 7, b: 100 - i}, i)).collect();
+    let btree: std::collections::BTreeMap<Test, i32> = data.clone().into_iter().collect();
+    assert_eq!(data.to_json_map_sorted_native().unwrap(), btree.to_json_map().unwrap());
+    assert_eq!(data.iter().to_json_map_sorted_native().unwrap(), btree.to_json_map().unwrap());
+  }
+
+
+  #[test]
+  fn test_serialize_with_custom_serializer() {
+    use serde::ser::{Serializer, SerializeSeq};
+    let mut map = std::collections::BTreeMap::<Test, i32>::new();
+    map.insert(Test {a: 3, b: 5}, 6);
+    let vec: Vec<(Test, i32)> = map.clone().into_iter().collect();
+
+    // embed the maps as elements of a hand-built, pretty-printed array
+    let mut buf = Vec::new();
+    let mut serializer = serde_json::Serializer::pretty(&mut buf);
+    {
+      let mut seq = (&mut serializer).serialize_seq(None).unwrap();
+      seq.serialize_element(&SerializeConsumingIterWrapper::new(map.clone())).unwrap();
+      seq.end().unwrap();
+    }
+    let pretty = String::from_utf8(buf).unwrap();
+    assert!(pretty.starts_with("[\n  {\n    \"{\\\"a\\\":3,\\\"b\\\":5}\": 6\n  }"), "{}", pretty);
+
+    let mut buf = Vec::new();
+    any_key_map::serialize(&map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+    let mut vec_buf = Vec::new();
+    any_key_vec::serialize(&vec, &mut serde_json::Serializer::new(&mut vec_buf)).unwrap();
+    assert_eq!(buf, vec_buf);
+    assert_eq!(String::from_utf8(buf).unwrap(), map.to_json_map().unwrap());
+  }
+
+  #[test]
+  fn test_serialize_into() {
+    use serde::ser::{Serializer, SerializeMap};
+    let mut map = std::collections::BTreeMap::<Test, i32>::new();
+    map.insert(Test {a: 3, b: 5}, 6);
+    map.insert(Test {a: 7, b: 9}, 10);
+    let vec: Vec<(Test, i32)> = map.clone().into_iter().collect();
+
+    // each any-key map is the value of an entry in a hand-built outer map
+    let mut buf = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut buf);
+    {
+      struct Map<'a>(&'a std::collections::BTreeMap<Test, i32>);
+      impl Serialize for Map<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> { serialize_map_into(self.0, serializer) }
+      }
+      struct Vector<'a>(&'a Vec<(Test, i32)>);
+      impl Serialize for Vector<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> { serialize_vec_into(self.0, serializer) }
+      }
+      let mut outer = (&mut serializer).serialize_map(Some(3)).unwrap();
+      outer.serialize_entry("map", &Map(&map)).unwrap();
+      outer.serialize_entry("vec", &Vector(&vec)).unwrap();
+      outer.serialize_entry("consuming", &SerializeConsumingIterWrapper::new(vec.clone())).unwrap();
+      outer.end().unwrap();
+    }
+    let inner = map.to_json_map().unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), format!("{{\"map\":{0},\"vec\":{0},\"consuming\":{0}}}", inner));
+
+    let mut buf = Vec::new();
+    serialize_consuming_into(vec.clone(), &mut serde_json::Serializer::new(&mut buf)).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), inner);
+    let mut buf = Vec::new();
+    serialize_map_into(map.iter().map(|(k, v)| (k, v * 2)), &mut serde_json::Serializer::new(&mut buf)).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), r#"{"{\"a\":3,\"b\":5}":12,"{\"a\":7,\"b\":9}":20}"#);
+  }
+
+
+  #[test]
+  fn test_adversarial_input_is_error() {
+    let deep_key = format!("{{\"{}1{}\":1}}", "[".repeat(10_000), "]".repeat(10_000));
+    let deep_value = format!("{{\"1\":{}1{}}}", "[".repeat(10_000), "]".repeat(10_000));
+    let huge = format!("{{\"1{}\":1,\"2\":1{}}}", "0".repeat(400), "0".repeat(400));
+    for input in [deep_key.as_str(), deep_value.as_str(), huge.as_str(), "{\"1e999\":1}", "{\"\\ud800\":1}", "{", "", "\u{0}"] {
+      assert!(json_to_map::<Vec<serde_json::Value>, i32>(input).is_err(), "{}", input);
+      assert!(json_to_vec::<i32, u8>(input).is_err(), "{}", input);
+      assert!(json_to_vec_all::<f64, i32>(input).is_err(), "{}", input);
+      assert!(json_to_iter::<u64, i32>(input).map_or(true, |mut iter| iter.any(|entry| entry.is_err())), "{}", input);
+      assert!(JsonMapReader::<_, Vec<serde_json::Value>, i32>::new(input.as_bytes()).any(|entry| entry.is_err()), "{}", input);
+    }
+  }
+
+
+  // Serializes as a heterogeneous array, such as ["tag",5]
+  #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+  struct Tagged {
+    tag: String,
+    id: u32
+  }
+
+  impl Serialize for Tagged {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      (&self.tag, self.id).serialize(serializer)
+    }
+  }
+
+  impl<'de> Deserialize<'de> for Tagged {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      let (tag, id) = <(String, u32)>::deserialize(deserializer)?;
+      Ok(Tagged { tag, id })
+    }
+  }
+
+  #[test]
+  fn test_sequence_keys() {
+    let mut data = HashMap::<Tagged, i32>::new();
+    data.insert(Tagged { tag: "tag".to_string(), id: 5 }, 1);
+    data.insert(Tagged { tag: "a\"b".to_string(), id: 6 }, 2);
+    let serialized = data.to_json_map().unwrap();
+
+    // the array is kept as JSON text, rather than being treated like a string key
+    let raw: HashMap<String, i32> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(raw["[\"tag\",5]"], 1);
+    assert_eq!(raw["[\"a\\\"b\",6]"], 2);
+    assert_eq!(json_to_map::<Tagged, i32>(&serialized).unwrap(), data);
+    let vec: Vec<(Tagged, i32)> = data.clone().into_iter().collect();
+    assert_eq!(vec.to_json_map().unwrap().len(), serialized.len());
+    assert_eq!(data.to_json_map_uniform().unwrap().len(), serialized.len());
+  }
+
+
+  #[derive(Serialize, Deserialize, PartialEq, Debug)]
+  struct Meta {
+    version: u32,
+    key_type: String
+  }
+
+  #[test]
+  fn test_with_meta() {
+    let mut data = HashMap::<Test, i32>::new();
+    data.insert(Test {a: 3, b: 5}, 6);
+    let meta = Meta { version: 2, key_type: "Test".to_string() };
+    let serialized = data.to_json_map_with_meta(&meta).unwrap();
+    assert!(serialized.starts_with("{\"__meta\":{\"version\":2,\"key_type\":\"Test\"},"), "{}", serialized);
+
+    let (deser_meta, deser) = json_to_map_with_meta::<Meta, Test, i32>(&serialized).unwrap();
+    assert_eq!(deser_meta, meta);
+    assert_eq!(deser, data);
+    // the metadata is an ordinary entry to the other functions
+    assert!(json_to_map::<Test, i32>(&serialized).is_err());
+
+    let err = json_to_map_with_meta::<Meta, Test, i32>(&data.to_json_map().unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "missing \"__meta\" key");
+    let err = json_to_map_with_meta::<Meta, Test, i32>("{\"__meta\":1}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid value for key \"__meta\""), "{}", err);
+
+    let mut reserved = HashMap::<String, i32>::new();
+    reserved.insert("__meta".to_string(), 1);
+    let err = reserved.to_json_map_with_meta(()).unwrap_err();
+    assert_eq!(err.to_string(), "key conflicts with the reserved \"__meta\" key");
+  }
+
+
+  #[test]
+  fn test_json_to_btreemap() {
+    let data: std::collections::BTreeMap<Test, String> = (0..50).map(|i| (Test {a: i, b: -i}, format!("v{}", i))).collect();
+    let serialized = data.to_json_map().unwrap();
+    assert_eq!(json_to_btreemap::<Test, String>(&serialized).unwrap(), data);
+
+    // escaped keys cannot be borrowed, but are still parsed
+    let mut strings = std::collections::BTreeMap::<String, u64>::new();
+    strings.insert("a\"b\\n".to_string(), u64::MAX);
+    strings.insert("plain".to_string(), 1);
+    assert_eq!(json_to_btreemap::<String, u64>(&strings.to_json_map().unwrap()).unwrap(), strings);
+
+    let deser = json_to_btreemap::<u64, i32>("{\"2\":1,\"1\":2,\"2\":3}").unwrap();
+    assert_eq!(deser.into_iter().collect::<Vec<_>>(), vec![(1, 2), (2, 3)]);
+
+    let err = json_to_btreemap::<u64, i32>("{\"1\":1,\"-1\":2}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"-1\""), "{}", err);
+    assert!(err.to_string().contains(" at entry 1"), "{}", err);
+    let err = json_to_btreemap::<u64, i32>("{\"1\":1,\"2\":\"x\"}").unwrap_err();
+    assert!(err.to_string().contains(" at entry 1"), "{}", err);
+    assert!(json_to_btreemap::<u64, i32>("{\"1\":1} x").is_err());
+    assert!(json_to_btreemap::<u64, i32>("[]").is_err());
+  }
+
+
+  #[test]
+  fn test_json_to_map_strict_numeric() {
+    let data: HashMap<i64, i32> = [(0, 1), (-1, 2), (i64::MIN, 3), (i64::MAX, 4), (10, 5)].into_iter().collect();
+    let serialized = data.to_json_map().unwrap();
+    assert_eq!(json_to_map_strict_numeric::<i64, i32>(&serialized).unwrap(), data);
+
+    for key in ["-0", " 7", "7 ", "007", "+7", "", "-", "7.0", "1e2", "0x7"] {
+      let input = format!("{{{:?}:1}}", key);
+      let err = json_to_map_strict_numeric::<i64, i32>(&input).unwrap_err();
+      assert_eq!(err.to_string(), format!("invalid key {:?}: key is not a canonical integer", key));
+    }
+    // canonical, but out of range
+    let err = json_to_map_strict_numeric::<u8, i32>("{\"256\":1}").unwrap_err();
+    assert!(err.to_string().starts_with("invalid key \"256\""), "{}", err);
+  }
+
+
+  #[test]
+  fn test_rwlock_read_guard() {
+    let shared = std::sync::Arc::new(std::sync::RwLock::new(HashMap::<Test, i32>::new()));
+    shared.write().unwrap().insert(Test {a: 3, b: 5}, 6);
+    let expected = "{\"{\\\"a\\\":3,\\\"b\\\":5}\":6}";
+
+    let guard = shared.read().unwrap();
+    assert_eq!(guard.iter().to_json_map().unwrap(), expected);
+    assert_eq!(guard.to_json_map().unwrap(), expected);
+    assert_eq!((&*guard).to_json_map_sorted_native().unwrap(), expected);
+    drop(guard);
+
+    let other = std::sync::Arc::clone(&shared);
+    let ser = std::thread::spawn(move || other.read().unwrap().to_json_map().unwrap()).join().unwrap();
+    assert_eq!(ser, expected);
+
+    let vec_lock = std::sync::RwLock::new(vec![(Test {a: 3, b: 5}, 6)]);
+    assert_eq!(vec_lock.read().unwrap().iter().to_json_map().unwrap(), expected);
+  }
+
+
+  #[test]
+  fn test_struct_iter_map() {
+    let mut data = HashMap::<Test, Test>::new();
+    data.insert(Test {a: 3, b: 5}, Test {a: 7, b: 9});
+    data.insert(Test {a: 11, b: 1}, Test {a: 13, b: 14});
+    let ser = data.to_json_map().unwrap();
+
+    let deser: HashMap<i32, (i32, Test)> = json_to_iter_map(&ser, |(k, v): (Test, Test)| (k.a, (k.b, v))).unwrap()
+      .collect::<Result<_, _>>().unwrap();
+    assert_eq!(deser.len(), 2);
+    assert_eq!(deser[&3], (5, Test {a: 7, b: 9}));
+    assert_eq!(deser[&11], (1, Test {a: 13, b: 14}));
+
+    let mut calls = 0;
+    let results: Vec<_> = json_to_iter_map(r#"{"{\"a\":1,\"b\":2}":"bad","{\"a\":3,\"b\":5}":{"a":7,"b":9}}"#, |(k, v): (Test, Test)| { calls += 1; (v, k) }).unwrap().collect();
+    assert!(results[0].is_err());
+    assert_eq!(results[1].as_ref().unwrap(), &(Test {a: 7, b: 9}, Test {a: 3, b: 5}));
+    assert_eq!(calls, 1);
+
+    assert!(json_to_iter_map("[]", |(k, v): (Test, Test)| (v, k)).is_err());
+  }
+
+
+  #[test]
+  fn test_struct_value_with_custom_field_serializers() {
+    fn to_hex<S: serde::Serializer>(x: &i32, s: S) -> Result<S::Ok, S::Error> {
+      s.serialize_str(&format!("{:#x}", x))
+    }
+    fn from_hex<'de, D: serde::Deserializer<'de>>(d: D) -> Result<i32, D::Error> {
+      let s = String::deserialize(d)?;
+      i32::from_str_radix(s.trim_start_matches("0x"), 16).map_err(serde::de::Error::custom)
+    }
+    fn to_csv<S: serde::Serializer>(x: &[i32], s: S) -> Result<S::Ok, S::Error> {
+      s.serialize_str(&x.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(","))
+    }
+    fn from_csv<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<i32>, D::Error> {
+      let s = String::deserialize(d)?;
+      s.split(',').map(|i| i.parse().map_err(serde::de::Error::custom)).collect()
+    }
+
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct Custom {
+      #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+      pub a: i32,
+      #[serde(serialize_with = "to_csv", deserialize_with = "from_csv")]
+      pub b: Vec<i32>
+    }
+
+    #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Debug)]
+    struct SerdeWithMap {
+      #[serde(with = "any_key_map")]
+      pub inner: HashMap<Test, Custom>
+    }
+
+    let mut data = HashMap::<Test, Custom>::new();
+    data.insert(Test {a: 3, b: 5}, Custom {a: 255, b: vec![7, 9]});
+
+    let ser = data.to_json_map().unwrap();
+    assert_eq!(ser, r#"{"{\"a\":3,\"b\":5}":{"a":"0xff","b":"7,9"}}"#);
+    let deser: HashMap<Test, Custom> = json_to_map(&ser).unwrap();
+    assert_eq!(deser, data);
+
+    let nested = SerdeWithMap { inner: data };
+    let ser_nested = serde_json::to_string(&nested).unwrap();
+    assert_eq!(ser_nested, format!(r#"{{"inner":{}}}"#, ser));
+    let deser_nested: SerdeWithMap = serde_json::from_str(&ser_nested).unwrap();
+    assert_eq!(deser_nested, nested);
+
+    let err = json_to_map::<Test, Custom>(r#"{"{\"a\":3,\"b\":5}":{"a":"0xzz","b":"7,9"}}"#).unwrap_err();
+    assert!(err.to_string().contains("invalid digit"), "{}", err);
+  }
 }